@@ -1,3 +1,4 @@
+use crate::models::ShellCore;
 
 pub mod ls;
 pub mod cd;
@@ -13,4 +14,145 @@ pub mod exit;
 pub mod lseek;
 pub mod mv;
 pub mod rm;
-pub mod rmdir;
\ No newline at end of file
+pub mod rmdir;
+pub mod cat;
+pub mod grep;
+pub mod wc;
+pub mod sort;
+pub mod head;
+pub mod set;
+pub mod echo;
+pub mod unset;
+pub mod alias;
+pub mod unalias;
+pub mod fsck;
+pub mod label;
+pub mod df;
+pub mod tree;
+pub mod du;
+pub mod find;
+pub mod cp;
+pub mod lsdel;
+pub mod undelete;
+pub mod wipe_free;
+pub mod attrib;
+pub mod commit;
+pub mod discard;
+pub mod diff_overlay;
+
+/// Collects the bytes a text filter works on: the named files, concatenated,
+/// or the piped input when no files are given.
+pub fn filter_input(cmd: &str, files: &[&str], input: Option<&[u8]>, shell: &mut ShellCore) -> Option<Vec<u8>> {
+    if files.is_empty() {
+        return match input {
+            Some(data) => Some(data.to_vec()),
+            None => {
+                eprintln!("{}: no input (give a file name or pipe into it)", cmd);
+                None
+            }
+        };
+    }
+
+    let mut data = Vec::new();
+    for name in files {
+        let cwd = shell.cwd_cluster;
+        let entry = match shell.vol.resolve_entry(cwd, name) {
+            Some(e) => e,
+            None => {
+                eprintln!("{}: {}: No such file", cmd, name);
+                return None;
+            }
+        };
+        if entry.is_dir() {
+            eprintln!("{}: {}: Is a directory", cmd, name);
+            return None;
+        }
+
+        match shell.vol.read_file(entry.first_cluster, entry.size) {
+            Ok(bytes) => data.extend_from_slice(&bytes),
+            Err(e) => {
+                eprintln!("{}: {}: failed to read file: {}", cmd, name, e);
+                return None;
+            }
+        }
+    }
+    Some(data)
+}
+
+/// Formats a volume serial number the way DOS prints it, e.g. `1A2B-3C4D`.
+pub fn format_serial(serial: u32) -> String {
+    format!("{:04X}-{:04X}", serial >> 16, serial & 0xFFFF)
+}
+
+/// Formats a byte count with a binary unit suffix, e.g. `1.5K` or `64M`.
+pub fn human_size(bytes: u64) -> String {
+    const UNITS: [&str; 5] = ["K", "M", "G", "T", "P"];
+    if bytes < 1024 {
+        return bytes.to_string();
+    }
+    let mut value = bytes as f64;
+    let mut unit = "";
+    for u in UNITS {
+        if value < 1024.0 {
+            break;
+        }
+        value /= 1024.0;
+        unit = u;
+    }
+    if value < 10.0 && value.fract() != 0.0 {
+        format!("{:.1}{}", value, unit)
+    } else {
+        format!("{:.0}{}", value, unit)
+    }
+}
+
+/// Matches a name against a shell glob: `*` matches any run of characters,
+/// `?` any single character and `[...]` one character from a set or range
+/// (`[!...]` or `[^...]` negates it).
+pub fn glob_match(pattern: &str, name: &str, ignore_case: bool) -> bool {
+    let fold = |c: char| if ignore_case { c.to_ascii_uppercase() } else { c };
+    let pattern: Vec<char> = pattern.chars().map(fold).collect();
+    let name: Vec<char> = name.chars().map(fold).collect();
+    glob_match_from(&pattern, &name)
+}
+
+fn glob_match_from(pattern: &[char], name: &[char]) -> bool {
+    match pattern.first() {
+        None => name.is_empty(),
+        Some('*') => (0..=name.len()).any(|skip| glob_match_from(&pattern[1..], &name[skip..])),
+        Some('?') => !name.is_empty() && glob_match_from(&pattern[1..], &name[1..]),
+        Some('[') => {
+            let Some(&c) = name.first() else { return false };
+            match match_class(&pattern[1..], c) {
+                Some((matched, rest)) => matched && glob_match_from(rest, &name[1..]),
+                // An unclosed '[' is an ordinary character
+                None => c == '[' && glob_match_from(&pattern[1..], &name[1..]),
+            }
+        }
+        Some(&p) => name.first() == Some(&p) && glob_match_from(&pattern[1..], &name[1..]),
+    }
+}
+
+/// Matches `c` against a bracket expression whose '[' is already consumed and
+/// returns the result with the pattern after the closing ']'.
+fn match_class(class: &[char], c: char) -> Option<(bool, &[char])> {
+    let negated = matches!(class.first(), Some('!') | Some('^'));
+    let mut i = if negated { 1 } else { 0 };
+    let mut matched = false;
+    let mut first = true;
+
+    while i < class.len() {
+        if class[i] == ']' && !first {
+            return Some((matched != negated, &class[i + 1..]));
+        }
+        if i + 2 < class.len() && class[i + 1] == '-' && class[i + 2] != ']' {
+            matched |= class[i] <= c && c <= class[i + 2];
+            i += 3;
+        } else {
+            matched |= class[i] == c;
+            i += 1;
+        }
+        first = false;
+    }
+    None
+}
@@ -0,0 +1,38 @@
+use std::io::Write;
+
+use crate::models::ShellCore;
+
+
+/// `alias NAME=value` defines an alias, `alias NAME` prints one and `alias`
+/// alone lists them all.
+pub fn alias(args: &[String], shell: &mut ShellCore, out: &mut dyn Write) -> i32 {
+    if args.is_empty() {
+        for (name, value) in shell.aliases.iter() {
+            writeln!(out, "alias {}='{}'", name, value).ok();
+        }
+        return 0;
+    }
+
+    let mut status = 0;
+    for arg in args {
+        match arg.split_once('=') {
+            Some((name, value)) if !name.is_empty() && !name.contains('/') => {
+                shell.aliases.insert(name.to_string(), value.to_string());
+            }
+            Some(_) => {
+                eprintln!("alias: invalid alias name: {}", arg);
+                status = 1;
+            }
+            None => match shell.aliases.get(arg) {
+                Some(value) => {
+                    writeln!(out, "alias {}='{}'", arg, value).ok();
+                }
+                None => {
+                    eprintln!("alias: {}: not found", arg);
+                    status = 1;
+                }
+            },
+        }
+    }
+    status
+}
@@ -0,0 +1,143 @@
+use std::collections::HashSet;
+use std::io::Write;
+
+use crate::builtins::ls::attr_string;
+use crate::models::ShellCore;
+
+/// The bits to set and clear, and whether to descend into directories.
+struct Change {
+    set: u8,
+    clear: u8,
+    recursive: bool,
+}
+
+const USAGE: &str = "usage: attrib [+r|-r] [+h|-h] [+s|-s] [+a|-a] [-R] PATH...";
+
+/// `attrib [+r|-r] [+h|-h] [+s|-s] [+a|-a] [-R] PATH...` sets or clears the
+/// read-only, hidden, system and archive bits of each entry, or prints them
+/// when no change is asked for. `-R` also applies to everything below a
+/// directory.
+pub fn attrib(args: &[String], shell: &mut ShellCore, out: &mut dyn Write) -> i32 {
+    let mut set = 0u8;
+    let mut clear = 0u8;
+    let mut recursive = false;
+    let mut paths: Vec<&str> = Vec::new();
+    for arg in args {
+        if arg == "-R" {
+            recursive = true;
+            continue;
+        }
+        let (sign, letters) = match arg.split_at_checked(1) {
+            Some(("+", rest)) if !rest.is_empty() => ('+', rest),
+            Some(("-", rest)) if !rest.is_empty() => ('-', rest),
+            _ => {
+                paths.push(arg);
+                continue;
+            }
+        };
+        for c in letters.chars() {
+            let bit = match c {
+                'r' | 'R' => 0x01,
+                'h' | 'H' => 0x02,
+                's' | 'S' => 0x04,
+                'a' | 'A' => 0x20,
+                other => {
+                    eprintln!("attrib: unknown attribute: {}{}\n{}", sign, other, USAGE);
+                    return 2;
+                }
+            };
+            if sign == '+' {
+                set |= bit;
+                clear &= !bit;
+            } else {
+                clear |= bit;
+                set &= !bit;
+            }
+        }
+    }
+    if paths.is_empty() {
+        eprintln!("attrib: missing path\n{}", USAGE);
+        return 2;
+    }
+
+    let change = Change { set, clear, recursive };
+    let mut status = 0;
+    for path in paths {
+        let trimmed = path.trim_end_matches('/');
+        let (dir, name) = match trimmed.rfind('/') {
+            Some(i) => (&trimmed[..i + 1], &trimmed[i + 1..]),
+            None => ("", trimmed),
+        };
+        if name.is_empty() || name == "." || name == ".." {
+            eprintln!("attrib: {}: the root, '.' and '..' have no attributes of their own", path);
+            status = 1;
+            continue;
+        }
+        let cwd = shell.cwd_cluster;
+        let Some(parent) = shell.vol.resolve_directory(cwd, dir) else {
+            eprintln!("attrib: {}: no such file or directory", path);
+            status = 1;
+            continue;
+        };
+        let mut visited = HashSet::new();
+        if let Err(e) = apply(shell, parent, name, trimmed, &change, &mut visited, out) {
+            eprintln!("attrib: {}", e);
+            status = 1;
+        }
+    }
+    status
+}
+
+/// Changes, or prints when there is nothing to change, the attributes of
+/// `name` in `dir`, then of everything below it when the change is recursive.
+fn apply(
+    shell: &mut ShellCore,
+    dir: u32,
+    name: &str,
+    path: &str,
+    change: &Change,
+    visited: &mut HashSet<u32>,
+    out: &mut dyn Write,
+) -> Result<(), String> {
+    let (cluster, offset) = shell
+        .vol
+        .find_entry_in_directory(dir, name)
+        .ok_or_else(|| format!("{}: no such file or directory", path))?;
+    let mut raw = shell
+        .vol
+        .read_raw_entry(cluster, offset)
+        .map_err(|e| format!("{}: failed to read directory entry: {}", path, e))?;
+    if (raw[11] & 0x08) != 0 {
+        return Err(format!("{}: is the volume label", path));
+    }
+
+    if change.set == 0 && change.clear == 0 {
+        writeln!(out, "{} {}", attr_string(raw[11]), path).ok();
+    } else {
+        raw[11] = (raw[11] | change.set) & !change.clear;
+        shell
+            .vol
+            .write_raw_entry(cluster, offset, &raw)
+            .map_err(|e| format!("{}: failed to write directory entry: {}", path, e))?;
+    }
+
+    let hi = u16::from_le_bytes([raw[20], raw[21]]) as u32;
+    let lo = u16::from_le_bytes([raw[26], raw[27]]) as u32;
+    let first = (hi << 16) | lo;
+    // A directory that links back to an ancestor is only walked once
+    if !change.recursive || (raw[11] & 0x10) == 0 || first < 2 || !visited.insert(first) {
+        return Ok(());
+    }
+    let children = shell
+        .vol
+        .list_directory(first)
+        .map_err(|e| format!("{}: failed to read directory: {}", path, e))?;
+    for child in children {
+        if child.name == "." || child.name == ".." {
+            continue;
+        }
+        let child_path = format!("{}/{}", path, child.name);
+        apply(shell, first, &child.name, &child_path, change, visited, out)?;
+    }
+    Ok(())
+}
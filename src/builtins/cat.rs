@@ -0,0 +1,19 @@
+use std::io::Write;
+
+use crate::builtins::filter_input;
+use crate::models::ShellCore;
+
+
+pub fn cat(files: &[&str], input: Option<&[u8]>, shell: &mut ShellCore, out: &mut dyn Write) -> i32 {
+    if files.is_empty() && input.is_none() {
+        eprintln!("cat: missing file name");
+        return 1;
+    }
+    match filter_input("cat", files, input, shell) {
+        Some(data) => {
+            out.write_all(&data).ok();
+            0
+        }
+        None => 1,
+    }
+}
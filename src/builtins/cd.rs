@@ -1,20 +1,23 @@
 use crate::models::ShellCore;
 
 
-pub fn cd(target_dir: &str, shell: &mut ShellCore) {
-    if target_dir == "" {
+pub fn cd(target_dir: &str, shell: &mut ShellCore) -> i32 {
+    if target_dir.is_empty() {
         eprintln!("cd: missing operand");
-        return;
+        return 1;
     }
     if target_dir == "." {
-        return;
+        return 0;
+    }
+    if target_dir.contains('/') {
+        return cd_path(target_dir, shell);
     }
 
     if target_dir == ".." {
         let root = shell.vol.bpb.bpb_root_clus;
         let mut cluster = shell.cwd_cluster;
         if cluster == root {
-            return;
+            return 0;
         }
 
         loop {
@@ -44,16 +47,16 @@ pub fn cd(target_dir: &str, shell: &mut ShellCore) {
 
                     shell.cwd_cluster = if parent_cluster == 0 { root } else { parent_cluster };
 
-                    if shell.cwd_path != "/" {
-                        if let Some(pos) = shell.cwd_path.rfind('/') {
-                            if pos == 0 {
-                                shell.cwd_path = "/".to_string();
-                            } else {
-                                shell.cwd_path.truncate(pos);
-                            }
+                    if shell.cwd_path != "/"
+                        && let Some(pos) = shell.cwd_path.rfind('/')
+                    {
+                        if pos == 0 {
+                            shell.cwd_path = "/".to_string();
+                        } else {
+                            shell.cwd_path.truncate(pos);
                         }
                     }
-                    return;
+                    return 0;
                 }
             }
 
@@ -63,7 +66,7 @@ pub fn cd(target_dir: &str, shell: &mut ShellCore) {
             }
             cluster = next;
         }
-        return;
+        return 1;
     }
     
     let mut cwd_cluster = shell.cwd_cluster as usize;
@@ -101,7 +104,7 @@ pub fn cd(target_dir: &str, shell: &mut ShellCore) {
 
             
         }
-        let next = shell.vol.fat[cwd_cluster as usize] as usize;
+        let next = shell.vol.fat[cwd_cluster] as usize;
 
         if next >= 0x0FFFFFF8 {
             eprintln!("cd: no such directory: {}", target_dir);
@@ -142,7 +145,7 @@ pub fn cd(target_dir: &str, shell: &mut ShellCore) {
                     let is_dir = (attr & 0x10) != 0;
                     if !is_dir {
                         eprintln!("cd: not a directory: {}", target_dir);
-                        return;
+                        return 1;
                     }
                     let high_cluster = u16::from_le_bytes([buffer[offset + 20], buffer[offset + 21]]);
                     let low_cluster = u16::from_le_bytes([buffer[offset + 26], buffer[offset + 27]]);
@@ -156,12 +159,12 @@ pub fn cd(target_dir: &str, shell: &mut ShellCore) {
                         }
                         shell.cwd_path.push_str(&short_name);
                     }
-                    return;
+                    return 0;
                 }
 
             }
         }
-        let next = shell.vol.fat[cwd_cluster as usize] as usize;
+        let next = shell.vol.fat[cwd_cluster] as usize;
 
         if next >= 0x0FFFFFF8 {
             break;
@@ -169,4 +172,35 @@ pub fn cd(target_dir: &str, shell: &mut ShellCore) {
 
         cwd_cluster = next;
     }
+    1
+}
+
+/// Changes into a multi-component path such as `/LOGS/2024` or `../LIBS/`.
+fn cd_path(path: &str, shell: &mut ShellCore) -> i32 {
+    let cluster = match shell.vol.resolve_directory(shell.cwd_cluster, path) {
+        Some(c) => c,
+        None => {
+            eprintln!("cd: no such directory: {}", path);
+            return 1;
+        }
+    };
+
+    let mut components: Vec<String> = if path.starts_with('/') {
+        Vec::new()
+    } else {
+        shell.cwd_path.split('/').filter(|c| !c.is_empty()).map(String::from).collect()
+    };
+    for component in path.split('/') {
+        match component {
+            "" | "." => {}
+            ".." => {
+                components.pop();
+            }
+            name => components.push(name.to_ascii_uppercase()),
+        }
+    }
+
+    shell.cwd_cluster = cluster;
+    shell.cwd_path = format!("/{}", components.join("/"));
+    0
 }
@@ -1,10 +1,12 @@
 use crate::models::ShellCore;
 
 
-pub fn close(file_descriptor: usize, shell: &mut ShellCore) {
+pub fn close(file_descriptor: usize, shell: &mut ShellCore) -> i32 {
     if let Some(pos) = shell.open_files.iter().position(|of| of.file_descriptor == file_descriptor) {
         shell.open_files.remove(pos);
     } else {
         eprintln!("close: file not open: {}", file_descriptor);
+        return 1;
     }
+    0
 }
@@ -0,0 +1,31 @@
+use std::io::Write;
+
+use crate::models::ShellCore;
+
+/// `commit` writes everything the session changed in its overlay into the
+/// image and starts the overlay over empty.
+pub fn commit(args: &[String], shell: &mut ShellCore, out: &mut dyn Write) -> i32 {
+    if !args.is_empty() {
+        eprintln!("commit: usage: commit");
+        return 2;
+    }
+    let Some(overlay) = &shell.vol.overlay else {
+        eprintln!("commit: no overlay in use (start the shell with --overlay FILE)");
+        return 1;
+    };
+    let path = overlay.path.display().to_string();
+    if shell.read_only {
+        eprintln!("commit: the image is mounted read-only; the changes stay in {}", path);
+        return 1;
+    }
+    match shell.vol.commit_overlay() {
+        Ok(n) => {
+            writeln!(out, "Committed {} sectors from {} to the image", n, path).ok();
+            0
+        }
+        Err(e) => {
+            eprintln!("commit: failed to write the image: {}", e);
+            1
+        }
+    }
+}
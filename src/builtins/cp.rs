@@ -0,0 +1,150 @@
+use std::collections::HashSet;
+
+use crate::models::{DirEntry, ShellCore};
+
+/// `cp [-r] [-p] SRC DST` copies a file to a new name, giving the copy its own
+/// cluster chain. When DST is an existing directory the copy goes inside it
+/// under the source's name. `-r` copies a directory and everything below it
+/// and `-p` keeps the source's timestamps. If the volume fills up partway
+/// nothing is left behind.
+pub fn cp(args: &[String], shell: &mut ShellCore) -> i32 {
+    let mut recursive = false;
+    let mut preserve = false;
+    let mut operands: Vec<&str> = Vec::new();
+    for arg in args {
+        match arg.strip_prefix('-') {
+            Some(flags) if !flags.is_empty() => {
+                for flag in flags.chars() {
+                    match flag {
+                        'r' | 'R' => recursive = true,
+                        'p' => preserve = true,
+                        other => {
+                            eprintln!("cp: unknown option: -{}", other);
+                            return 2;
+                        }
+                    }
+                }
+            }
+            _ => operands.push(arg),
+        }
+    }
+    let [src, dst] = operands[..] else {
+        eprintln!("cp: usage: cp [-r] [-p] SRC DST");
+        return 2;
+    };
+
+    let cwd = shell.cwd_cluster;
+    let Some(source) = shell.vol.resolve_entry(cwd, src) else {
+        eprintln!("cp: {}: no such file or directory", src);
+        return 1;
+    };
+    if source.is_dir() && !recursive {
+        eprintln!("cp: -r not specified; omitting directory {}", src);
+        return 1;
+    }
+    let src_trimmed = src.trim_end_matches('/');
+    let src_dir = src_trimmed.rfind('/').map_or("", |i| &src_trimmed[..i + 1]);
+    let src_parent = shell.vol.resolve_directory(cwd, src_dir).unwrap_or(cwd);
+
+    let (dst_parent, dst_name, dst_path) = match shell.vol.resolve_entry(cwd, dst) {
+        Some(target) if target.is_dir() => {
+            if source.name == "/" {
+                eprintln!("cp: cannot copy the root directory into itself");
+                return 1;
+            }
+            let path = format!("{}/{}", dst.trim_end_matches('/'), source.name);
+            (target.first_cluster, source.name.clone(), path)
+        }
+        _ => {
+            let trimmed = dst.trim_end_matches('/');
+            let (dst_dir, dst_name) = match trimmed.rfind('/') {
+                Some(i) => (&trimmed[..i + 1], &trimmed[i + 1..]),
+                None => ("", trimmed),
+            };
+            let Some(dst_parent) = shell.vol.resolve_directory(cwd, dst_dir) else {
+                eprintln!("cp: {}: no such directory", dst_dir);
+                return 1;
+            };
+            if dst_name.is_empty() || dst_name == "." || dst_name == ".." {
+                eprintln!("cp: invalid destination name: {}", dst);
+                return 1;
+            }
+            (dst_parent, dst_name.to_string(), dst.to_string())
+        }
+    };
+    if shell.vol.find_entry_in_directory(dst_parent, &dst_name).is_some() {
+        eprintln!("cp: {} already exists", dst_path);
+        return 1;
+    }
+    if source.is_dir() && shell.vol.ancestors(dst_parent).contains(&source.first_cluster) {
+        eprintln!("cp: cannot copy {} into itself", src);
+        return 1;
+    }
+
+    // The FAT only reaches the disk at the end, so putting this copy back
+    // undoes every allocation made by a copy that fails partway
+    let fat_before = shell.vol.fat.clone();
+    if let Err(e) = copy_entry(shell, src_parent, &source, dst_parent, &dst_name, preserve, &mut HashSet::new()) {
+        shell.vol.fat = fat_before;
+        eprintln!("cp: failed to copy {}: {}", src, e);
+        return 1;
+    }
+    if let Err(e) = shell.vol.flush_fat() {
+        eprintln!("cp: failed to flush FAT: {}", e);
+        return 1;
+    }
+    0
+}
+
+/// Copies a file, or a directory and everything in it, into `dst_parent` as
+/// `name`. The new entry is written last, once everything it points to is in
+/// place, so a copy that fails leaves no entry behind.
+fn copy_entry(
+    shell: &mut ShellCore,
+    src_parent: u32,
+    source: &DirEntry,
+    dst_parent: u32,
+    name: &str,
+    preserve: bool,
+    visited: &mut HashSet<u32>,
+) -> std::io::Result<()> {
+    let first = if source.is_dir() {
+        if source.first_cluster < 2 || source.first_cluster > shell.vol.max_cluster() {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!("{}: directory points at invalid cluster {}", source.name, source.first_cluster),
+            ));
+        }
+        // A directory that links back to an ancestor would be copied forever
+        if !visited.insert(source.first_cluster) {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!("{}: directory loops back on itself; run fsck", source.name),
+            ));
+        }
+        let dir = shell.vol.alloc_cluster().ok_or_else(|| {
+            std::io::Error::new(std::io::ErrorKind::StorageFull, "no free clusters left on the volume")
+        })?;
+        shell.vol.initialize_directory_cluster(dir, dst_parent);
+        for child in shell.vol.list_directory(source.first_cluster)? {
+            if child.name == "." || child.name == ".." {
+                continue;
+            }
+            copy_entry(shell, source.first_cluster, &child, dir, &child.name, preserve, visited)?;
+        }
+        dir
+    } else {
+        shell.vol.copy_chain(source.first_cluster)?
+    };
+
+    let mut entry = [0u8; 32];
+    shell.vol.write_directory_entry(&mut entry, name, source.attr, first, source.size);
+    if preserve && let Some((cluster, offset)) = shell.vol.find_entry_in_directory(src_parent, &source.name) {
+        // Creation, access and modification times
+        let original = shell.vol.read_raw_entry(cluster, offset)?;
+        entry[13..20].copy_from_slice(&original[13..20]);
+        entry[22..26].copy_from_slice(&original[22..26]);
+    }
+    shell.vol.add_raw_entry(dst_parent, &entry)?;
+    Ok(())
+}
@@ -1,9 +1,9 @@
 use crate::models::ShellCore;
 
-pub fn creat(filename: &str, shell: &mut ShellCore) {
+pub fn creat(filename: &str, shell: &mut ShellCore) -> i32 {
     if filename.is_empty() {
         eprintln!("creat: missing filename :(");
-        return;
+        return 1;
     }
 
     let parent_cluster = shell.cwd_cluster;
@@ -14,7 +14,7 @@ pub fn creat(filename: &str, shell: &mut ShellCore) {
         .is_some()
     {
         eprintln!("creat: file already exists: {}", filename);
-        return;
+        return 1;
     }
 
     let bytes_per_sector = shell.vol.bpb.bpb_byts_per_sec as usize;
@@ -30,7 +30,7 @@ pub fn creat(filename: &str, shell: &mut ShellCore) {
                     }
                     None => {
                         eprintln!("creat: no free clusters available for parent directory :(");
-                        return;
+                        return 1;
                     }
                 }
             }
@@ -45,7 +45,7 @@ pub fn creat(filename: &str, shell: &mut ShellCore) {
 
         if sector_index_in_cluster >= sectors_per_cluster {
             eprintln!("creat: internal error: entry offset outside cluster :(");
-            return;
+            return 1;
         }
 
         let first_sector = shell.vol.get_first_sector_of_cluster(entry_cluster);
@@ -54,12 +54,12 @@ pub fn creat(filename: &str, shell: &mut ShellCore) {
         let mut sector_buf = vec![0u8; bytes_per_sector];
         if let Err(e) = shell.vol.read_sector(sector_number, &mut sector_buf) {
             eprintln!("creat: failed to read parent directory sector: {e}");
-            return;
+            return 1;
         }
 
         if entry_offset_in_sector + 32 > bytes_per_sector {
             eprintln!("creat: internal error: entry crosses sector boundary :(");
-            return;
+            return 1;
         }
 
         let entry_slice =
@@ -75,12 +75,13 @@ pub fn creat(filename: &str, shell: &mut ShellCore) {
 
         if let Err(e) = shell.vol.write_sector(sector_number, &sector_buf) {
             eprintln!("creat: failed to write parent directory sector: {e}");
-            return;
+            return 1;
         }
     }
 
     if let Err(e) = shell.vol.flush_fat() {
         eprintln!("creat: failed to flush FAT to disk: {e}");
-        return;
+        return 1;
     }
+    0
 }
\ No newline at end of file
@@ -0,0 +1,52 @@
+use std::io::Write;
+
+use crate::builtins::human_size;
+use crate::models::ShellCore;
+
+
+/// Prints total, used and free space counted from the FAT, and warns when the
+/// free count cached in FSInfo disagrees. `-h` prints sizes as 1.5K, 64M, ...
+pub fn df(human: bool, shell: &mut ShellCore, out: &mut dyn Write) -> i32 {
+    let vol = &mut shell.vol;
+    let cluster_bytes = vol.bpb.bpb_byts_per_sec as u64 * vol.bpb.bpb_sec_per_clus as u64;
+    let max_cluster = vol.max_cluster();
+
+    let total = max_cluster - 1;
+    let free = vol.free_clusters();
+    let bad = (2..=max_cluster).filter(|&c| vol.fat[c as usize] & 0x0FFFFFFF == 0x0FFFFFF7).count() as u32;
+    let used = total - free - bad;
+
+    let size = |clusters: u32| {
+        let bytes = clusters as u64 * cluster_bytes;
+        if human { human_size(bytes) } else { bytes.to_string() }
+    };
+
+    writeln!(out, "{:<6} {:>10} {:>12}", "", "Clusters", "Bytes").ok();
+    writeln!(out, "{:<6} {:>10} {:>12}", "Total", total, size(total)).ok();
+    writeln!(out, "{:<6} {:>10} {:>12}", "Used", used, size(used)).ok();
+    writeln!(out, "{:<6} {:>10} {:>12}", "Free", free, size(free)).ok();
+    if bad > 0 {
+        writeln!(out, "{:<6} {:>10} {:>12}", "Bad", bad, size(bad)).ok();
+    }
+    writeln!(out, "Use%: {}%", (used as u64 * 100).div_ceil(total.max(1) as u64)).ok();
+
+    match vol.read_fsinfo() {
+        Ok(Some((0xFFFFFFFF, _))) => {
+            writeln!(out, "FSInfo: free count not set").ok();
+        }
+        Ok(Some((cached, _))) if cached != free => {
+            writeln!(out, "FSInfo: reports {} free clusters, the FAT has {} (fsck --repair updates it)", cached, free).ok();
+        }
+        Ok(Some(_)) => {
+            writeln!(out, "FSInfo: free count matches the FAT").ok();
+        }
+        Ok(None) => {
+            writeln!(out, "FSInfo: missing or invalid").ok();
+        }
+        Err(e) => {
+            eprintln!("df: failed to read FSInfo: {}", e);
+            return 1;
+        }
+    }
+    0
+}
@@ -0,0 +1,141 @@
+use std::collections::{BTreeMap, HashSet};
+use std::io::{Seek, SeekFrom, Write};
+
+use crate::models::{BootSector, ShellCore, Volume};
+
+/// What the comparison needs to know about one entry in either view.
+struct Seen {
+    is_dir: bool,
+    attr: u8,
+    first_cluster: u32,
+    size: u32,
+    modified: Option<i64>,
+    clusters: Vec<u32>,
+}
+
+/// `diff-overlay` lists the files and directories the overlay adds (A),
+/// modifies (M) or deletes (D) compared with the image on disk.
+pub fn diff_overlay(args: &[String], shell: &mut ShellCore, out: &mut dyn Write) -> i32 {
+    if !args.is_empty() {
+        eprintln!("diff-overlay: usage: diff-overlay");
+        return 2;
+    }
+    let Some(overlay) = &shell.vol.overlay else {
+        eprintln!("diff-overlay: no overlay in use (start the shell with --overlay FILE)");
+        return 1;
+    };
+    let sectors = overlay.sectors();
+
+    // A second view of the volume that reads the image without the overlay
+    let mut base = match shell.vol.file.try_clone().and_then(|mut file| {
+        file.seek(SeekFrom::Start(0))?;
+        let bpb = BootSector::new(&mut file);
+        Ok(Volume::new(file, bpb))
+    }) {
+        Ok(vol) => vol,
+        Err(e) => {
+            eprintln!("diff-overlay: failed to read the image: {}", e);
+            return 1;
+        }
+    };
+
+    match compare(shell, &mut base, &sectors) {
+        Ok(changes) if changes.is_empty() => {
+            writeln!(out, "No files differ from the image ({} sectors in the overlay)", sectors.len()).ok();
+            0
+        }
+        Ok(changes) => {
+            for (status, path) in changes {
+                writeln!(out, "{}  {}", status, path).ok();
+            }
+            0
+        }
+        Err(e) => {
+            eprintln!("diff-overlay: {}", e);
+            1
+        }
+    }
+}
+
+/// Walks both views and pairs up their entries by path.
+fn compare(shell: &mut ShellCore, base: &mut Volume, sectors: &[u32]) -> std::io::Result<Vec<(char, String)>> {
+    // Clusters with at least one sector that really differs from the image
+    let bytes_per_sector = base.bpb.bpb_byts_per_sec as usize;
+    let sectors_per_cluster = base.bpb.bpb_sec_per_clus as u32;
+    let first_data_sector = base.first_data_sector;
+    let mut changed = HashSet::new();
+    let mut ours = vec![0u8; bytes_per_sector];
+    let mut theirs = vec![0u8; bytes_per_sector];
+    for &sector in sectors.iter().filter(|&&s| s >= first_data_sector) {
+        shell.vol.read_sector(sector, &mut ours)?;
+        base.read_sector(sector, &mut theirs)?;
+        if ours != theirs {
+            changed.insert((sector - first_data_sector) / sectors_per_cluster + 2);
+        }
+    }
+
+    let root = base.bpb.bpb_root_clus;
+    let mut before = BTreeMap::new();
+    walk(base, root, "", &mut before, &mut HashSet::new())?;
+    let mut after = BTreeMap::new();
+    walk(&mut shell.vol, root, "", &mut after, &mut HashSet::new())?;
+
+    let mut changes = Vec::new();
+    for (path, new) in &after {
+        let shown = if new.is_dir { format!("{}/", path) } else { path.clone() };
+        match before.get(path) {
+            None => changes.push(('A', shown)),
+            Some(old) => {
+                let meta_differs = old.is_dir != new.is_dir
+                    || old.attr != new.attr
+                    || old.first_cluster != new.first_cluster
+                    || old.size != new.size
+                    || old.modified != new.modified;
+                // A directory's own clusters change with its entries, which are listed themselves
+                let data_differs = !new.is_dir && new.clusters.iter().any(|c| changed.contains(c));
+                if meta_differs || data_differs {
+                    changes.push(('M', shown));
+                }
+            }
+        }
+    }
+    for (path, old) in &before {
+        if !after.contains_key(path) {
+            changes.push(('D', if old.is_dir { format!("{}/", path) } else { path.clone() }));
+        }
+    }
+    changes.sort_by(|a, b| a.1.cmp(&b.1));
+    Ok(changes)
+}
+
+fn walk(
+    vol: &mut Volume,
+    dir: u32,
+    prefix: &str,
+    seen: &mut BTreeMap<String, Seen>,
+    visited: &mut HashSet<u32>,
+) -> std::io::Result<()> {
+    if !visited.insert(dir) {
+        return Ok(());
+    }
+    for entry in vol.list_directory(dir)? {
+        if entry.name == "." || entry.name == ".." {
+            continue;
+        }
+        let path = format!("{}/{}", prefix, entry.long_name.as_deref().unwrap_or(&entry.name));
+        let is_dir = entry.is_dir();
+        if is_dir && entry.first_cluster >= 2 {
+            walk(vol, entry.first_cluster, &path, seen, visited)?;
+        }
+        let clusters = if is_dir { Vec::new() } else { vol.chain_clusters(entry.first_cluster) };
+        seen.insert(path, Seen {
+            is_dir,
+            attr: entry.attr,
+            first_cluster: entry.first_cluster,
+            size: entry.size,
+            modified: entry.modified,
+            clusters,
+        });
+    }
+    Ok(())
+}
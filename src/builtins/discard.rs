@@ -0,0 +1,35 @@
+use std::io::Write;
+
+use crate::models::ShellCore;
+
+/// `discard` throws away everything the session changed in its overlay,
+/// going back to the image as it is on disk.
+pub fn discard(args: &[String], shell: &mut ShellCore, out: &mut dyn Write) -> i32 {
+    if !args.is_empty() {
+        eprintln!("discard: usage: discard");
+        return 2;
+    }
+    if shell.vol.overlay.is_none() {
+        eprintln!("discard: no overlay in use (start the shell with --overlay FILE)");
+        return 1;
+    }
+    // Open files and the working directory may not exist in the image
+    if !shell.open_files.is_empty() {
+        eprintln!("discard: close the open files first");
+        return 1;
+    }
+    let dropped = match shell.vol.discard_overlay() {
+        Ok(n) => n,
+        Err(e) => {
+            eprintln!("discard: failed to reset the overlay: {}", e);
+            return 1;
+        }
+    };
+    writeln!(out, "Discarded {} sectors", dropped).ok();
+    if shell.cwd_path != "/" {
+        shell.cwd_cluster = shell.vol.bpb.bpb_root_clus;
+        shell.cwd_path = String::from("/");
+        writeln!(out, "The working directory is now /").ok();
+    }
+    0
+}
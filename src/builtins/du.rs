@@ -0,0 +1,119 @@
+use std::collections::HashSet;
+use std::io::Write;
+
+use crate::builtins::human_size;
+use crate::models::ShellCore;
+
+
+/// `du [-s] [-h] [path]` prints, for every directory below `path`, the sum of
+/// the file sizes inside it (logical) and the space its clusters take up
+/// (allocated), including the directories' own clusters. `-s` prints only the
+/// total for `path`, `-h` prints sizes as 1.5K, 64M, ...
+pub fn du(args: &[String], shell: &mut ShellCore, out: &mut dyn Write) -> i32 {
+    let mut summarize = false;
+    let mut human = false;
+    let mut path: Option<&str> = None;
+    for arg in args {
+        match arg.as_str() {
+            "-s" => summarize = true,
+            "-h" => human = true,
+            "-sh" | "-hs" => {
+                summarize = true;
+                human = true;
+            }
+            other if other.starts_with('-') => {
+                eprintln!("du: unknown option: {}", other);
+                return 2;
+            }
+            other if path.is_none() => path = Some(other),
+            _ => {
+                eprintln!("du: too many arguments");
+                return 2;
+            }
+        }
+    }
+
+    let path = path.unwrap_or(".");
+    let cwd = shell.cwd_cluster;
+    let Some(entry) = shell.vol.resolve_entry(cwd, path) else {
+        eprintln!("du: {}: no such file or directory", path);
+        return 1;
+    };
+
+    let mut walk = DuWalk {
+        summarize,
+        human,
+        cluster_bytes: shell.vol.bpb.bpb_byts_per_sec as u64 * shell.vol.bpb.bpb_sec_per_clus as u64,
+        visited: HashSet::new(),
+    };
+    writeln!(out, "{:>12} {:>12}  Path", "Logical", "Allocated").ok();
+
+    if !entry.is_dir() {
+        let allocated = shell.vol.chain_length(entry.first_cluster) as u64 * walk.cluster_bytes;
+        walk.print(entry.size as u64, allocated, path, out);
+        return 0;
+    }
+
+    match walk.visit(shell, entry.first_cluster, path.trim_end_matches('/').to_string(), out) {
+        Ok((logical, allocated)) => {
+            if summarize {
+                walk.print(logical, allocated, path, out);
+            }
+            0
+        }
+        Err(e) => {
+            eprintln!("du: failed to read directory: {}", e);
+            1
+        }
+    }
+}
+
+struct DuWalk {
+    summarize: bool,
+    human: bool,
+    cluster_bytes: u64,
+    visited: HashSet<u32>, // guards against directories that link back to an ancestor
+}
+
+impl DuWalk {
+    /// Returns the logical and allocated bytes of a directory and everything in it.
+    fn visit(&mut self, shell: &mut ShellCore, cluster: u32, path: String, out: &mut dyn Write) -> std::io::Result<(u64, u64)> {
+        if !self.visited.insert(cluster) {
+            return Ok((0, 0));
+        }
+        if cluster < 2 || cluster > shell.vol.max_cluster() {
+            eprintln!("du: {}: directory points at invalid cluster {}; skipping it", path, cluster);
+            return Ok((0, 0));
+        }
+        let mut logical = 0u64;
+        let mut allocated = shell.vol.chain_length(cluster) as u64 * self.cluster_bytes;
+
+        for entry in shell.vol.list_directory(cluster)? {
+            if entry.name == "." || entry.name == ".." {
+                continue;
+            }
+            if entry.is_dir() {
+                let child_path = if path.is_empty() { format!("/{}", entry.name) } else { format!("{}/{}", path, entry.name) };
+                let (l, a) = self.visit(shell, entry.first_cluster, child_path, out)?;
+                logical += l;
+                allocated += a;
+            } else {
+                logical += entry.size as u64;
+                allocated += shell.vol.chain_length(entry.first_cluster) as u64 * self.cluster_bytes;
+            }
+        }
+
+        if !self.summarize {
+            self.print(logical, allocated, if path.is_empty() { "/" } else { &path }, out);
+        }
+        Ok((logical, allocated))
+    }
+
+    fn print(&self, logical: u64, allocated: u64, path: &str, out: &mut dyn Write) {
+        if self.human {
+            writeln!(out, "{:>12} {:>12}  {}", human_size(logical), human_size(allocated), path).ok();
+        } else {
+            writeln!(out, "{:>12} {:>12}  {}", logical, allocated, path).ok();
+        }
+    }
+}
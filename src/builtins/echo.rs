@@ -0,0 +1,7 @@
+use std::io::Write;
+
+
+pub fn echo(args: &[String], out: &mut dyn Write) -> i32 {
+    writeln!(out, "{}", args.join(" ")).ok();
+    0
+}
@@ -1,5 +1,5 @@
 
-pub fn exit() {
+pub fn exit(code: i32) -> i32 {
     println!("Exiting core module.");
-    std::process::exit(0);
+    std::process::exit(code)
 }
@@ -0,0 +1,251 @@
+use std::collections::HashSet;
+use std::io::Write;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::builtins::{glob_match, rm::remove_entry};
+use crate::commands::{is_built_in, run_built_in};
+use crate::models::{DirEntry, ShellCore};
+
+const USAGE: &str = "usage: find [path] [-name GLOB] [-iname GLOB] [-type f|d] [-size [+-]N[ckMG]] \
+[-newer PATH] [-mtime [+-]N] [-attr RHSAD] [-delete] [-exec BUILTIN ARGS {} ';']";
+
+#[derive(Clone, Copy)]
+enum Compare {
+    Less,
+    Equal,
+    Greater,
+}
+
+impl Compare {
+    /// Splits a leading '+' or '-' off a numeric argument, as find does.
+    fn parse(arg: &str) -> (Compare, &str) {
+        if let Some(rest) = arg.strip_prefix('+') {
+            (Compare::Greater, rest)
+        } else if let Some(rest) = arg.strip_prefix('-') {
+            (Compare::Less, rest)
+        } else {
+            (Compare::Equal, arg)
+        }
+    }
+
+    fn holds(&self, value: i64, target: i64) -> bool {
+        match self {
+            Compare::Less => value < target,
+            Compare::Equal => value == target,
+            Compare::Greater => value > target,
+        }
+    }
+}
+
+enum Test {
+    Name { pattern: String, ignore_case: bool },
+    Type { dir: bool },
+    Size { compare: Compare, units: i64, unit_bytes: i64 },
+    Newer(i64),
+    Mtime { compare: Compare, days: i64 },
+    Attr(u8),
+}
+
+enum Action {
+    Print,
+    Delete,
+    Exec(Vec<String>),
+}
+
+/// A matching entry, with the directory it lives in so it can be deleted.
+/// The starting point has no parent.
+struct Found {
+    path: String,
+    parent: Option<u32>,
+    entry: DirEntry,
+}
+
+/// Walks the tree below a path and prints, deletes or runs a builtin on every
+/// entry that passes all the tests. With no action the paths are printed.
+pub fn find(args: &[String], shell: &mut ShellCore, out: &mut dyn Write) -> i32 {
+    let (start, tests, mut actions) = match parse(args, shell) {
+        Ok(parsed) => parsed,
+        Err(e) => {
+            eprintln!("find: {}\n{}", e, USAGE);
+            return 2;
+        }
+    };
+    if actions.is_empty() {
+        actions.push(Action::Print);
+    }
+
+    let cwd = shell.cwd_cluster;
+    let Some(root) = shell.vol.resolve_entry(cwd, &start) else {
+        eprintln!("find: {}: no such file or directory", start);
+        return 1;
+    };
+
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs() as i64).unwrap_or(0);
+    let mut found = Vec::new();
+    let mut visited = HashSet::new();
+    let start_path = start.trim_end_matches('/').to_string();
+    if let Err(e) = walk(shell, root, None, start_path, &mut visited, &mut |f: Found| {
+        if tests.iter().all(|t| passes(t, &f.entry, now)) {
+            found.push(f);
+        }
+    }) {
+        eprintln!("find: failed to read directory: {}", e);
+        return 1;
+    }
+
+    // Deleting has to empty a directory before the directory itself goes
+    if actions.iter().any(|a| matches!(a, Action::Delete)) {
+        found.reverse();
+    }
+
+    let mut status = 0;
+    for f in found {
+        let path = if f.path.is_empty() { String::from("/") } else { f.path };
+        for action in &actions {
+            match action {
+                Action::Print => {
+                    writeln!(out, "{}", path).ok();
+                }
+                Action::Delete => {
+                    // Like find, the starting point itself is never deleted
+                    let Some(parent) = f.parent else { continue };
+                    if let Err(e) = remove_entry(shell, parent, &f.entry.name) {
+                        eprintln!("find: {}: {}", path, e);
+                        status = 1;
+                    }
+                }
+                Action::Exec(command) => {
+                    let args: Vec<String> = command[1..].iter().map(|a| a.replace("{}", &path)).collect();
+                    if run_built_in(&command[0], shell, &args, None, out) != 0 {
+                        status = 1;
+                    }
+                }
+            }
+        }
+    }
+    status
+}
+
+fn parse(args: &[String], shell: &mut ShellCore) -> Result<(String, Vec<Test>, Vec<Action>), String> {
+    let mut start: Option<String> = None;
+    let mut tests = Vec::new();
+    let mut actions = Vec::new();
+    let mut iter = args.iter();
+
+    while let Some(arg) = iter.next() {
+        let mut value = |name: &str| iter.next().cloned().ok_or_else(|| format!("{} needs an argument", name));
+        match arg.as_str() {
+            "-name" => tests.push(Test::Name { pattern: value("-name")?, ignore_case: false }),
+            "-iname" => tests.push(Test::Name { pattern: value("-iname")?, ignore_case: true }),
+            "-type" => match value("-type")?.as_str() {
+                "f" => tests.push(Test::Type { dir: false }),
+                "d" => tests.push(Test::Type { dir: true }),
+                other => return Err(format!("unknown -type {}", other)),
+            },
+            "-size" => {
+                let raw = value("-size")?;
+                let (compare, rest) = Compare::parse(&raw);
+                // Without a suffix the size counts 512-byte blocks, as in find
+                let (digits, unit_bytes) = match rest.chars().last() {
+                    Some('c') => (&rest[..rest.len() - 1], 1),
+                    Some('k') => (&rest[..rest.len() - 1], 1 << 10),
+                    Some('M') => (&rest[..rest.len() - 1], 1 << 20),
+                    Some('G') => (&rest[..rest.len() - 1], 1 << 30),
+                    _ => (rest, 512),
+                };
+                let units = digits.parse().map_err(|_| format!("invalid -size {}", raw))?;
+                tests.push(Test::Size { compare, units, unit_bytes });
+            }
+            "-newer" => {
+                let path = value("-newer")?;
+                let cwd = shell.cwd_cluster;
+                let entry = shell.vol.resolve_entry(cwd, &path).ok_or_else(|| format!("{}: no such file or directory", path))?;
+                tests.push(Test::Newer(entry.modified.unwrap_or(i64::MIN)));
+            }
+            "-mtime" => {
+                let raw = value("-mtime")?;
+                let (compare, rest) = Compare::parse(&raw);
+                let days = rest.parse().map_err(|_| format!("invalid -mtime {}", raw))?;
+                tests.push(Test::Mtime { compare, days });
+            }
+            "-attr" => {
+                let mut mask = 0u8;
+                for c in value("-attr")?.chars() {
+                    mask |= match c.to_ascii_lowercase() {
+                        'r' => 0x01,
+                        'h' => 0x02,
+                        's' => 0x04,
+                        'd' => 0x10,
+                        'a' => 0x20,
+                        other => return Err(format!("unknown attribute {}", other)),
+                    };
+                }
+                tests.push(Test::Attr(mask));
+            }
+            "-print" => actions.push(Action::Print),
+            "-delete" => actions.push(Action::Delete),
+            "-exec" => {
+                // An unquoted `;` ends the whole command list, so the terminator
+                // is quoted as ';' or left off entirely
+                let command: Vec<String> = iter.by_ref().take_while(|a| *a != ";" && *a != "+").cloned().collect();
+                match command.first() {
+                    Some(name) if is_built_in(name) => actions.push(Action::Exec(command)),
+                    Some(name) => return Err(format!("-exec only runs builtins, not {}", name)),
+                    None => return Err(String::from("-exec needs a command")),
+                }
+            }
+            other if other.starts_with('-') => return Err(format!("unknown option {}", other)),
+            other if start.is_none() && tests.is_empty() && actions.is_empty() => start = Some(other.to_string()),
+            other => return Err(format!("unexpected argument {}", other)),
+        }
+    }
+
+    Ok((start.unwrap_or_else(|| String::from(".")), tests, actions))
+}
+
+fn passes(test: &Test, entry: &DirEntry, now: i64) -> bool {
+    match test {
+        Test::Name { pattern, ignore_case } => glob_match(pattern, &entry.name, *ignore_case),
+        Test::Type { dir } => entry.is_dir() == *dir,
+        Test::Size { compare, units, unit_bytes } => {
+            let size = (entry.size as i64 + unit_bytes - 1) / unit_bytes;
+            compare.holds(size, *units)
+        }
+        Test::Newer(than) => entry.modified.is_some_and(|m| m > *than),
+        Test::Mtime { compare, days } => {
+            entry.modified.is_some_and(|m| compare.holds((now - m).div_euclid(86400), *days))
+        }
+        Test::Attr(mask) => entry.attr & mask == *mask,
+    }
+}
+
+/// Visits `entry` and everything below it in directory order.
+fn walk(
+    shell: &mut ShellCore,
+    entry: DirEntry,
+    parent: Option<u32>,
+    path: String,
+    visited: &mut HashSet<u32>,
+    visit: &mut dyn FnMut(Found),
+) -> std::io::Result<()> {
+    let is_dir = entry.is_dir();
+    let cluster = entry.first_cluster;
+    visit(Found { path: path.clone(), parent, entry });
+
+    // A directory that links back to an ancestor is only listed once
+    if !is_dir || !visited.insert(cluster) {
+        return Ok(());
+    }
+    if cluster < 2 || cluster > shell.vol.max_cluster() {
+        eprintln!("find: {}: directory points at invalid cluster {}; skipping it", path, cluster);
+        return Ok(());
+    }
+    for child in shell.vol.list_directory(cluster)? {
+        if child.name == "." || child.name == ".." {
+            continue;
+        }
+        let child_path = format!("{}/{}", path, child.name);
+        walk(shell, child, Some(cluster), child_path, visited, visit)?;
+    }
+    Ok(())
+}
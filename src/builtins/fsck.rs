@@ -0,0 +1,124 @@
+use std::io::Write;
+
+use crate::fsck::{apply, check, plan, Category, Report};
+use crate::models::ShellCore;
+
+// Each pass fixes chains first and checks again, so a volume normally settles in two
+const MAX_REPAIR_PASSES: usize = 4;
+
+/// Checks the whole volume and prints the problems found, grouped by kind.
+/// With `--repair` the problems are fixed, and `--dry-run` lists the repairs
+/// without writing anything. `--save-lost` keeps lost chains as
+/// /FOUND.000/FILEnnnn.CHK instead of freeing them. Returns 1 when problems
+/// remain.
+pub fn fsck(args: &[String], shell: &mut ShellCore, out: &mut dyn Write) -> i32 {
+    let mut repair = false;
+    let mut dry_run = false;
+    let mut save_lost = false;
+    for arg in args {
+        match arg.as_str() {
+            "--repair" => repair = true,
+            "--dry-run" => dry_run = true,
+            "--save-lost" => save_lost = true,
+            other => {
+                eprintln!("fsck: unknown option: {}", other);
+                return 2;
+            }
+        }
+    }
+
+    let mut report = match check(&mut shell.vol) {
+        Ok(r) => r,
+        Err(e) => {
+            eprintln!("fsck: failed to read volume: {}", e);
+            return 2;
+        }
+    };
+    print_report(&report, out);
+    if report.is_clean() || !(repair || dry_run) {
+        return if report.is_clean() { 0 } else { 1 };
+    }
+
+    let bytes_per_cluster = shell.vol.bpb.bpb_byts_per_sec as u32 * shell.vol.bpb.bpb_sec_per_clus as u32;
+
+    if dry_run {
+        writeln!(out, "Planned repairs (dry run, nothing is written):").ok();
+        for planned in plan(&report, bytes_per_cluster, save_lost) {
+            writeln!(out, "  {}", planned).ok();
+        }
+        writeln!(out, "  recompute the FSInfo free cluster count").ok();
+        return 1;
+    }
+
+    writeln!(out, "Repairing:").ok();
+    for _ in 0..MAX_REPAIR_PASSES {
+        let mut repairs = plan(&report, bytes_per_cluster, save_lost);
+        if repairs.iter().any(|r| r.is_structural()) {
+            repairs.retain(|r| r.is_structural());
+        }
+        if repairs.is_empty() {
+            break;
+        }
+
+        for repair in &repairs {
+            writeln!(out, "  {}", repair).ok();
+        }
+        if let Err(e) = apply(&mut shell.vol, &repairs) {
+            eprintln!("fsck: repair failed: {}", e);
+            return 2;
+        }
+
+        report = match check(&mut shell.vol) {
+            Ok(r) => r,
+            Err(e) => {
+                eprintln!("fsck: failed to read volume: {}", e);
+                return 2;
+            }
+        };
+    }
+
+    match shell.vol.update_fsinfo() {
+        Ok(free) => writeln!(out, "  FSInfo updated: {} free clusters", free).ok(),
+        Err(e) => {
+            eprintln!("fsck: failed to update FSInfo: {}", e);
+            return 2;
+        }
+    };
+
+    if report.is_clean() {
+        writeln!(out, "fsck: volume repaired").ok();
+        return 0;
+    }
+    writeln!(out, "fsck: problems left after repair:").ok();
+    print_problems(&report, out);
+    1
+}
+
+fn print_report(report: &Report, out: &mut dyn Write) {
+    writeln!(
+        out,
+        "fsck: {} directories, {} files, {} clusters in use",
+        report.directories, report.files, report.used_clusters
+    ).ok();
+
+    if report.is_clean() {
+        writeln!(out, "fsck: no problems found").ok();
+        return;
+    }
+    print_problems(report, out);
+}
+
+fn print_problems(report: &Report, out: &mut dyn Write) {
+    let mut categories: Vec<Category> = report.problems.iter().map(|p| p.category()).collect();
+    categories.sort();
+    categories.dedup();
+
+    for category in categories {
+        let problems: Vec<_> = report.problems.iter().filter(|p| p.category() == category).collect();
+        writeln!(out, "{} ({}):", category.title(), problems.len()).ok();
+        for problem in problems {
+            writeln!(out, "  {}", problem).ok();
+        }
+    }
+    writeln!(out, "fsck: {} problem(s) found", report.problems.len()).ok();
+}
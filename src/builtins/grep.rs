@@ -0,0 +1,70 @@
+use std::io::Write;
+
+use crate::builtins::filter_input;
+use crate::models::ShellCore;
+
+
+/// Prints the lines containing a fixed-string pattern.
+/// Flags: -i (ignore case), -v (invert), -n (line numbers), -c (count only).
+pub fn grep(args: &[String], input: Option<&[u8]>, shell: &mut ShellCore, out: &mut dyn Write) -> i32 {
+    let mut ignore_case = false;
+    let mut invert = false;
+    let mut line_numbers = false;
+    let mut count_only = false;
+    let mut operands: Vec<&str> = Vec::new();
+
+    for arg in args {
+        match arg.as_str() {
+            "-i" => ignore_case = true,
+            "-v" => invert = true,
+            "-n" => line_numbers = true,
+            "-c" => count_only = true,
+            flag if flag.starts_with('-') && flag.len() > 1 => {
+                eprintln!("grep: unknown option: {}", flag);
+                return 1;
+            }
+            operand => operands.push(operand),
+        }
+    }
+
+    let pattern = match operands.first() {
+        Some(p) => *p,
+        None => {
+            eprintln!("grep: missing pattern");
+            return 1;
+        }
+    };
+    let needle = if ignore_case { pattern.to_ascii_lowercase() } else { pattern.to_string() };
+
+    let data = match filter_input("grep", &operands[1..], input, shell) {
+        Some(d) => d,
+        None => return 1,
+    };
+    let text = String::from_utf8_lossy(&data);
+
+    let mut count = 0;
+    for (i, line) in text.lines().enumerate() {
+        let found = if ignore_case {
+            line.to_ascii_lowercase().contains(&needle)
+        } else {
+            line.contains(&needle)
+        };
+        if found == invert {
+            continue;
+        }
+        count += 1;
+        if count_only {
+            continue;
+        }
+        if line_numbers {
+            writeln!(out, "{}:{}", i + 1, line).ok();
+        } else {
+            writeln!(out, "{}", line).ok();
+        }
+    }
+
+    if count_only {
+        writeln!(out, "{}", count).ok();
+    }
+    if count == 0 { 1 } else { 0 }
+}
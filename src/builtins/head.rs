@@ -0,0 +1,44 @@
+use std::io::Write;
+
+use crate::builtins::filter_input;
+use crate::models::ShellCore;
+
+
+/// Prints the first lines of the input, 10 unless `-n N` (or `-N`) is given.
+pub fn head(args: &[String], input: Option<&[u8]>, shell: &mut ShellCore, out: &mut dyn Write) -> i32 {
+    let mut count: usize = 10;
+    let mut files: Vec<&str> = Vec::new();
+
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        if arg == "-n" {
+            match iter.next().and_then(|n| n.parse().ok()) {
+                Some(n) => count = n,
+                None => {
+                    eprintln!("head: -n needs a line count");
+                    return 1;
+                }
+            }
+        } else if let Some(n) = arg.strip_prefix('-').filter(|n| !n.is_empty()) {
+            match n.parse() {
+                Ok(n) => count = n,
+                Err(_) => {
+                    eprintln!("head: unknown option: {}", arg);
+                    return 1;
+                }
+            }
+        } else {
+            files.push(arg);
+        }
+    }
+
+    let data = match filter_input("head", &files, input, shell) {
+        Some(d) => d,
+        None => return 1,
+    };
+
+    for line in data.split_inclusive(|&b| b == b'\n').take(count) {
+        out.write_all(line).ok();
+    }
+    0
+}
@@ -1,12 +1,42 @@
+use std::io::Write;
+
+use crate::builtins::format_serial;
 use crate::models::BootSector;
 
 
-pub fn info(bsb: &BootSector) {
-    println!("Boot Sector Information:");
-    println!("Root Cluster: {}", bsb.bpb_root_clus);
-    println!("Bytes per Sector: {}", bsb.bpb_byts_per_sec);
-    println!("Sectors per Cluster: {}", bsb.bpb_sec_per_clus);
-    println!("Total Sectors: {}", bsb.bpb_tot_sec32);
-    println!("Sectors per FAT: {}", bsb.bpb_fatsz32);
-    println!("File Size: {} bytes", bsb.file_size);
+pub fn info(bsb: &BootSector, read_only: bool, out: &mut dyn Write) -> i32 {
+    writeln!(out, "Boot Sector Information:").ok();
+    writeln!(out, "Root Cluster: {}", bsb.bpb_root_clus).ok();
+    writeln!(out, "Bytes per Sector: {}", bsb.bpb_byts_per_sec).ok();
+    writeln!(out, "Sectors per Cluster: {}", bsb.bpb_sec_per_clus).ok();
+    writeln!(out, "Total Sectors: {}", bsb.bpb_tot_sec32).ok();
+    writeln!(out, "Sectors per FAT: {}", bsb.bpb_fatsz32).ok();
+    writeln!(out, "Reserved Sectors: {}", bsb.bpb_rsvd_sec_cnt).ok();
+    writeln!(out, "Number of FATs: {}", bsb.bpb_num_fats).ok();
+    writeln!(out, "Root Entry Count: {}", bsb.bpb_root_ent_cnt).ok();
+    writeln!(out, "Total Sectors (16-bit): {}", bsb.bpb_tot_sec16).ok();
+    writeln!(out, "Media Byte: 0x{:02X}", bsb.bpb_media).ok();
+    writeln!(out, "Sectors per FAT (16-bit): {}", bsb.bpb_fatsz16).ok();
+    writeln!(out, "Sectors per Track: {}", bsb.bpb_sec_per_trk).ok();
+    writeln!(out, "Number of Heads: {}", bsb.bpb_num_heads).ok();
+    writeln!(out, "Hidden Sectors: {}", bsb.bpb_hidd_sec).ok();
+    // Bit 7 set means only the FAT numbered in bits 0-3 is in use
+    let mirroring = if bsb.bpb_ext_flags & 0x80 != 0 {
+        format!("only FAT {} active", bsb.bpb_ext_flags & 0x0F)
+    } else {
+        String::from("mirrored")
+    };
+    writeln!(out, "Ext Flags: 0x{:04X} ({})", bsb.bpb_ext_flags, mirroring).ok();
+    writeln!(out, "FS Version: {}.{}", bsb.bpb_fs_ver >> 8, bsb.bpb_fs_ver & 0xFF).ok();
+    writeln!(out, "FSInfo Sector: {}", bsb.bpb_fs_info).ok();
+    writeln!(out, "Backup Boot Sector: {}", bsb.bpb_bk_boot_sec).ok();
+    writeln!(out, "Drive Number: 0x{:02X}", bsb.bs_drv_num).ok();
+    writeln!(out, "Boot Signature: 0x{:02X}", bsb.bs_boot_sig).ok();
+    writeln!(out, "File Size: {} bytes", bsb.file_size).ok();
+    writeln!(out, "OEM Name: {}", String::from_utf8_lossy(&bsb.bs_oem_name).trim_end()).ok();
+    writeln!(out, "Volume Label: {}", String::from_utf8_lossy(&bsb.bs_vol_lab).trim_end()).ok();
+    writeln!(out, "Volume Serial Number: {}", format_serial(bsb.bs_vol_id)).ok();
+    writeln!(out, "File System Type: {}", String::from_utf8_lossy(&bsb.bs_fil_sys_type).trim_end()).ok();
+    writeln!(out, "Mounted: {}", if read_only { "read-only" } else { "read-write" }).ok();
+    0
 }
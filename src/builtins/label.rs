@@ -0,0 +1,124 @@
+use std::io::Write;
+
+use crate::builtins::format_serial;
+use crate::mkfs::{label_bytes, valid_label, volume_serial};
+use crate::models::ShellCore;
+
+
+/// `label` prints the volume label and serial number, `label NEW` sets the
+/// label in both the boot sector and the root directory, `label --clear`
+/// removes it and `label --new-serial` gives the volume a fresh serial number.
+pub fn label(args: &[String], shell: &mut ShellCore, out: &mut dyn Write) -> i32 {
+    let mut new_label: Option<String> = None;
+    let mut clear = false;
+    let mut new_serial = false;
+    for arg in args {
+        match arg.as_str() {
+            "--clear" => clear = true,
+            "--new-serial" => new_serial = true,
+            other if other.starts_with('-') => {
+                eprintln!("label: unknown option: {}", other);
+                return 2;
+            }
+            other if new_label.is_none() => new_label = Some(other.to_ascii_uppercase()),
+            _ => {
+                eprintln!("label: too many arguments");
+                return 2;
+            }
+        }
+    }
+    if clear && new_label.is_some() {
+        eprintln!("label: --clear does not take a label");
+        return 2;
+    }
+
+    if let Some(new_label) = &new_label {
+        if !valid_label(new_label) {
+            eprintln!("label: invalid volume label: {}", new_label);
+            return 1;
+        }
+        if let Err(e) = set_label(shell, Some(new_label)) {
+            eprintln!("label: failed to write label: {}", e);
+            return 1;
+        }
+    } else if clear && let Err(e) = set_label(shell, None) {
+        eprintln!("label: failed to clear label: {}", e);
+        return 1;
+    }
+
+    if new_serial {
+        let serial = volume_serial();
+        if let Err(e) = shell.vol.write_boot_sector_field(67, &serial.to_le_bytes()) {
+            eprintln!("label: failed to write serial number: {}", e);
+            return 1;
+        }
+        shell.vol.bpb.bs_vol_id = serial;
+    }
+
+    if new_label.is_some() || clear || new_serial {
+        return 0;
+    }
+
+    let root_label = match read_root_label(shell) {
+        Ok(l) => l,
+        Err(e) => {
+            eprintln!("label: failed to read root directory: {}", e);
+            return 1;
+        }
+    };
+    let boot_label = String::from_utf8_lossy(&shell.vol.bpb.bs_vol_lab).trim_end().to_string();
+    let boot_label = if boot_label == "NO NAME" { None } else { Some(boot_label) };
+
+    match (&root_label, &boot_label) {
+        (Some(l), _) | (None, Some(l)) => writeln!(out, "Volume label: {}", l).ok(),
+        (None, None) => writeln!(out, "Volume has no label").ok(),
+    };
+    if root_label != boot_label {
+        // Set the label again to bring the two copies back in line
+        writeln!(
+            out,
+            "Boot sector label: {} (differs from the root directory)",
+            boot_label.as_deref().unwrap_or("NO NAME")
+        ).ok();
+    }
+    writeln!(out, "Serial number: {}", format_serial(shell.vol.bpb.bs_vol_id)).ok();
+    0
+}
+
+fn read_root_label(shell: &mut ShellCore) -> std::io::Result<Option<String>> {
+    match shell.vol.find_label_entry()? {
+        Some((cluster, offset)) => {
+            let entry = shell.vol.read_raw_entry(cluster, offset)?;
+            Ok(Some(String::from_utf8_lossy(&entry[0..11]).trim_end().to_string()))
+        }
+        None => Ok(None),
+    }
+}
+
+/// Writes the label to the boot sector, its backup and the root directory
+/// entry, or removes it from all three when `label` is None.
+fn set_label(shell: &mut ShellCore, label: Option<&str>) -> std::io::Result<()> {
+    let raw = label_bytes(label);
+    shell.vol.write_boot_sector_field(71, &raw)?;
+    shell.vol.bpb.bs_vol_lab = raw;
+
+    let existing = shell.vol.find_label_entry()?;
+    match (existing, label) {
+        (Some((cluster, offset)), Some(_)) => {
+            let mut entry = shell.vol.read_raw_entry(cluster, offset)?;
+            entry[0..11].copy_from_slice(&raw);
+            shell.vol.write_raw_entry(cluster, offset, &entry)?;
+        }
+        (Some((cluster, offset)), None) => shell.vol.mark_entry_deleted(cluster, offset)?,
+        (None, Some(_)) => {
+            let mut entry = [0u8; 32];
+            entry[0..11].copy_from_slice(&raw);
+            entry[11] = 0x08;
+            let root = shell.vol.bpb.bpb_root_clus;
+            shell.vol.add_raw_entry(root, &entry)?;
+            shell.vol.flush_fat()?;
+        }
+        (None, None) => {}
+    }
+    Ok(())
+}
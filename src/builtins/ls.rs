@@ -1,7 +1,10 @@
-use crate::models::ShellCore;
+use std::io::Write;
 
+use crate::models::ShellCore;
 
-pub fn ls(shell: &mut ShellCore) {
+/// Lists the current directory. Hidden and system entries only show up
+/// with `all`.
+pub fn ls(long: bool, all: bool, shell: &mut ShellCore, out: &mut dyn Write) -> i32 {
     let mut cwd_cluster = shell.cwd_cluster as usize;
     loop {
         let first_sector = shell.vol.get_first_sector_of_cluster(cwd_cluster as u32);
@@ -28,20 +31,29 @@ pub fn ls(shell: &mut ShellCore) {
                 if attr == 0x0F {
                     continue;  
                 }
+                if (attr & 0x08) != 0 {
+                    continue; // volume label
+                }
+                if !all && (attr & 0x06) != 0 {
+                    continue; // hidden or system
+                }
 
                 let short_name = shell.vol.parse_short_name(&buffer[offset..offset + 11]);
                 let is_dir = (attr & 0x10) != 0;
 
-                if is_dir {
-                    println!("[DIR]  {}", short_name);
+                if long {
+                    let size = u32::from_le_bytes([buffer[offset + 28], buffer[offset + 29], buffer[offset + 30], buffer[offset + 31]]);
+                    writeln!(out, "{} {:>10} {}", attr_string(attr), size, short_name).ok();
+                } else if is_dir {
+                    writeln!(out, "[DIR]  {}", short_name).ok();
                 } else {
-                    println!("[FILE] {}", short_name);
+                    writeln!(out, "[FILE] {}", short_name).ok();
                 }
             }
 
             
         }
-        let next = shell.vol.fat[cwd_cluster as usize] as usize;
+        let next = shell.vol.fat[cwd_cluster] as usize;
 
         if next >= 0x0FFFFFF8 {
             break;
@@ -49,4 +61,13 @@ pub fn ls(shell: &mut ShellCore) {
 
         cwd_cluster = next;
     }
+    0
+}
+
+pub fn attr_string(attr: u8) -> String {
+    let flags = [(0x10, 'd'), (0x01, 'r'), (0x02, 'h'), (0x04, 's'), (0x20, 'a')];
+    flags
+        .iter()
+        .map(|&(bit, c)| if attr & bit != 0 { c } else { '-' })
+        .collect()
 }
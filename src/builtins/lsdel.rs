@@ -0,0 +1,124 @@
+use std::io::Write;
+
+use crate::models::ShellCore;
+
+/// A deleted short entry, numbered by its slot in the directory so the
+/// numbers `lsdel` shows stay put as other entries are recovered.
+pub struct Deleted {
+    pub index: usize,
+    pub cluster: u32,
+    pub offset: usize,
+    pub raw: [u8; 32],
+}
+
+impl Deleted {
+    /// The 8.3 name with '?' standing in for the first character, which
+    /// deleting overwrote with 0xE5.
+    pub fn name(&self, shell: &ShellCore) -> String {
+        let mut raw_name = [0u8; 11];
+        raw_name.copy_from_slice(&self.raw[0..11]);
+        raw_name[0] = b'?';
+        shell.vol.parse_short_name(&raw_name)
+    }
+
+    pub fn first_cluster(&self) -> u32 {
+        let hi = u16::from_le_bytes([self.raw[20], self.raw[21]]) as u32;
+        let lo = u16::from_le_bytes([self.raw[26], self.raw[27]]) as u32;
+        (hi << 16) | lo
+    }
+
+    pub fn size(&self) -> u32 {
+        u32::from_le_bytes([self.raw[28], self.raw[29], self.raw[30], self.raw[31]])
+    }
+
+    pub fn is_dir(&self) -> bool {
+        (self.raw[11] & 0x10) != 0
+    }
+
+    /// Clusters the entry used, assuming it had no more than it needed. The
+    /// size of a directory is not recorded, so it counts as one cluster.
+    pub fn clusters_needed(&self, bytes_per_cluster: u32) -> u32 {
+        if self.first_cluster() == 0 {
+            0
+        } else if self.is_dir() {
+            1
+        } else {
+            self.size().div_ceil(bytes_per_cluster).max(1)
+        }
+    }
+}
+
+/// The deleted entries of a directory, skipping LFN slots, volume labels and
+/// entries that were scrubbed when they were deleted.
+pub fn deleted_entries(shell: &mut ShellCore, dir_cluster: u32) -> std::io::Result<Vec<Deleted>> {
+    let entries = shell
+        .vol
+        .raw_entries(dir_cluster)?
+        .into_iter()
+        .enumerate()
+        .filter(|(_, (_, _, raw))| raw[0] == 0xE5 && raw[11] != 0x0F && (raw[11] & 0x08) == 0)
+        .filter(|(_, (_, _, raw))| raw[1..].iter().any(|&b| b != 0))
+        .map(|(index, (cluster, offset, raw))| Deleted { index, cluster, offset, raw })
+        .collect();
+    Ok(entries)
+}
+
+/// How many clusters, counting contiguously from `first` and stopping at
+/// `needed`, are still free and so can still hold the deleted data.
+pub fn free_run(shell: &ShellCore, first: u32, needed: u32) -> u32 {
+    let max_cluster = shell.vol.max_cluster();
+    let mut run = 0;
+    while run < needed {
+        let cluster = first + run;
+        if cluster < 2 || cluster > max_cluster || shell.vol.fat[cluster as usize] & 0x0FFFFFFF != 0 {
+            break;
+        }
+        run += 1;
+    }
+    run
+}
+
+/// `lsdel [path]` lists the deleted entries of a directory with their size,
+/// first cluster and whether their clusters are still free to recover.
+pub fn lsdel(args: &[String], shell: &mut ShellCore, out: &mut dyn Write) -> i32 {
+    if args.len() > 1 {
+        eprintln!("lsdel: usage: lsdel [path]");
+        return 2;
+    }
+    let path = args.first().map(|s| s.as_str()).unwrap_or(".");
+    let cwd = shell.cwd_cluster;
+    let Some(dir) = shell.vol.resolve_directory(cwd, path) else {
+        eprintln!("lsdel: {}: no such directory", path);
+        return 1;
+    };
+    let deleted = match deleted_entries(shell, dir) {
+        Ok(d) => d,
+        Err(e) => {
+            eprintln!("lsdel: failed to read directory {}: {}", path, e);
+            return 1;
+        }
+    };
+    if deleted.is_empty() {
+        writeln!(out, "No deleted entries").ok();
+        return 0;
+    }
+
+    let bytes_per_cluster = shell.vol.bpb.bpb_byts_per_sec as u32 * shell.vol.bpb.bpb_sec_per_clus as u32;
+    writeln!(out, "{:>3}  {:<12} {:>10} {:>9}  Clusters", "#", "Name", "Size", "First").ok();
+    for d in &deleted {
+        let needed = d.clusters_needed(bytes_per_cluster);
+        let run = free_run(shell, d.first_cluster(), needed);
+        let state = if needed == 0 {
+            String::from("none")
+        } else if run == needed {
+            String::from("free")
+        } else if run == 0 {
+            String::from("reused")
+        } else {
+            format!("{} of {} free", run, needed)
+        };
+        let name = if d.is_dir() { format!("{}/", d.name(shell)) } else { d.name(shell) };
+        writeln!(out, "{:>3}  {:<12} {:>10} {:>9}  {}", d.index, name, d.size(), d.first_cluster(), state).ok();
+    }
+    0
+}
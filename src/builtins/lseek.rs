@@ -1,7 +1,7 @@
 use crate::models::ShellCore;
 
 
-pub fn lseek(file_descriptor: usize, offset: u32, shell: &mut ShellCore) {
+pub fn lseek(file_descriptor: usize, offset: u32, shell: &mut ShellCore) -> i32 {
     if let Some(of) = shell.open_files.iter_mut().find(|of| of.file_descriptor == file_descriptor) {
         if offset > of.size {
             of.offset = of.size;
@@ -10,5 +10,7 @@ pub fn lseek(file_descriptor: usize, offset: u32, shell: &mut ShellCore) {
         }
     } else {
         eprintln!("lseek: file not open: {}", file_descriptor);
+        return 1;
     }
+    0
 }
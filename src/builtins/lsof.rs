@@ -1,12 +1,14 @@
+use std::io::Write;
+
 use crate::models::{FileMode, ShellCore};
 
 
-pub fn lsof(shell: &ShellCore) {
+pub fn lsof(shell: &ShellCore, out: &mut dyn Write) -> i32 {
     if shell.open_files.is_empty() {
-        println!("No open files.");
-        return;
+        writeln!(out, "No open files.").ok();
+        return 0;
     }
-    println!("Open Files:");
+    writeln!(out, "Open Files:").ok();
     for of in shell.open_files.iter() {
         let mode_str = match of.mode {
             FileMode::Read => "r",
@@ -19,6 +21,7 @@ pub fn lsof(shell: &ShellCore) {
         } else {
             format!("{}/{}", dir_path, of.name)
         };
-        println!("Name: {}, Mode: {}, Offset: {}, Path: {}, FD: {}, Size: {}", of.name, mode_str, of.offset, full_path, of.file_descriptor, of.size);
+        writeln!(out, "Name: {}, Mode: {}, Offset: {}, Path: {}, FD: {}, Size: {}", of.name, mode_str, of.offset, full_path, of.file_descriptor, of.size).ok();
     }
+    0
 }
@@ -1,9 +1,9 @@
 use crate::models::ShellCore;
 
-pub fn mkdir(dirname: &str, shell: &mut ShellCore) {
+pub fn mkdir(dirname: &str, shell: &mut ShellCore) -> i32 {
     if dirname.is_empty() {
         eprintln!("mkdir: missing directory name :(");
-        return;
+        return 1;
     }
 
     let parent_cluster = shell.cwd_cluster;
@@ -14,7 +14,7 @@ pub fn mkdir(dirname: &str, shell: &mut ShellCore) {
         .is_some()
     {
         eprintln!("mkdir: directory already exists: {}", dirname);
-        return;
+        return 1;
     }
 
     let bytes_per_sector = shell.vol.bpb.bpb_byts_per_sec as usize;
@@ -31,7 +31,7 @@ pub fn mkdir(dirname: &str, shell: &mut ShellCore) {
                     }
                     None => {
                         eprintln!("mkdir: no free clusters available for parent directory :(");
-                        return;
+                        return 1;
                     }
                 }
             }
@@ -41,7 +41,7 @@ pub fn mkdir(dirname: &str, shell: &mut ShellCore) {
         Some(c) => c,
         None => {
             eprintln!("mkdir: no free clusters available for new directory :(");
-            return;
+            return 1;
         }
     };
 
@@ -51,7 +51,7 @@ pub fn mkdir(dirname: &str, shell: &mut ShellCore) {
 
         if sector_index_in_cluster >= sectors_per_cluster {
             eprintln!("mkdir: internal error: entry offset outside cluster :(");
-            return;
+            return 1;
         }
 
         let first_sector = shell.vol.get_first_sector_of_cluster(entry_cluster);
@@ -60,12 +60,12 @@ pub fn mkdir(dirname: &str, shell: &mut ShellCore) {
         let mut sector_buf = vec![0u8; bytes_per_sector];
         if let Err(e) = shell.vol.read_sector(sector_number, &mut sector_buf) {
             eprintln!("mkdir: failed to read parent directory sector: {e}");
-            return;
+            return 1;
         }
 
         if entry_offset_in_sector + 32 > bytes_per_sector {
             eprintln!("mkdir: internal error: entry crosses sector boundary :(");
-            return;
+            return 1;
         }
 
         let entry_slice =
@@ -77,7 +77,7 @@ pub fn mkdir(dirname: &str, shell: &mut ShellCore) {
 
         if let Err(e) = shell.vol.write_sector(sector_number, &sector_buf) {
             eprintln!("mkdir: failed to write parent directory sector: {e}");
-            return;
+            return 1;
         }
     }
 
@@ -87,6 +87,7 @@ pub fn mkdir(dirname: &str, shell: &mut ShellCore) {
 
     if let Err(e) = shell.vol.flush_fat() {
         eprintln!("mkdir: failed to flush FAT to disk: {e}");
-        return;
+        return 1;
     }
+    0
 }
\ No newline at end of file
@@ -1,102 +1,249 @@
+use std::io::{BufRead, IsTerminal, Write};
+
+use crate::core::check_modify;
 use crate::models::ShellCore;
 
-pub fn mv(shell: &mut ShellCore, src: &str, dest: &str) {
-    if src.is_empty() || dest.is_empty() {
-        eprintln!("mv: missing operand");
-        return;
+/// `mv [-f] [-n] [-i] SRC DST` renames SRC, moves it into DST when DST is an
+/// existing directory, or does both when DST is a path ending in a new name.
+/// An existing file at DST is replaced and its clusters freed; `-n` leaves it
+/// alone and `-i` asks first. Read-only and system entries, on either side,
+/// are only moved or replaced with `-f`. A moved directory has its ".." entry pointed at
+/// its new parent.
+///
+/// The old entry is always gone from disk before the new one is written, so
+/// an interrupted move can lose track of SRC's clusters (fsck finds them) but
+/// never leaves two entries sharing one chain.
+pub fn mv(args: &[String], shell: &mut ShellCore, out: &mut dyn Write) -> i32 {
+    let mut no_clobber = false;
+    let mut interactive = false;
+    let mut force = false;
+    let mut operands: Vec<&String> = Vec::new();
+    for arg in args {
+        match arg.strip_prefix('-') {
+            Some(flags) if !flags.is_empty() => {
+                // As in mv, whichever of -f, -n and -i comes last wins
+                for flag in flags.chars() {
+                    match flag {
+                        'f' => (force, no_clobber, interactive) = (true, false, false),
+                        'n' => (force, no_clobber, interactive) = (false, true, false),
+                        'i' => (force, no_clobber, interactive) = (false, false, true),
+                        other => {
+                            eprintln!("mv: unknown option: -{}", other);
+                            return 2;
+                        }
+                    }
+                }
+            }
+            _ => operands.push(arg),
+        }
     }
-    if src.eq_ignore_ascii_case(dest) {
-        eprintln!("mv: source and destination are the same");
-        return;
+    let [src, dest] = operands[..] else {
+        eprintln!("mv: missing operand");
+        return 1;
+    };
+
+    let cwd = shell.cwd_cluster;
+    let root = shell.vol.bpb.bpb_root_clus;
+    let (src_dir, src_name) = split_path(src);
+    if src_name.is_empty() || src_name == "." || src_name == ".." {
+        eprintln!("mv: cannot move '{}'", src);
+        return 1;
     }
+    let found = match shell.vol.resolve_directory(cwd, src_dir) {
+        Some(parent) => shell.vol.find_entry_in_directory(parent, src_name).map(|loc| (parent, loc)),
+        None => None,
+    };
+    let Some((src_parent, (src_cluster, src_offset))) = found else {
+        eprintln!("mv: cannot stat '{}': No such file or directory", src);
+        return 1;
+    };
 
     for of in shell.open_files.iter() {
-        if of.name.eq_ignore_ascii_case(src) {
+        if of.name.eq_ignore_ascii_case(src_name) && of.dir_cluster == src_parent {
             eprintln!("mv: cannot move open file '{}'", src);
-            return;
+            return 1;
         }
     }
 
-    let cwd = shell.cwd_cluster;
-
-    let (src_cluster, src_offset, src_entry) =
-        match shell.vol.find_entry_in_directory(cwd, src)
-    {
-        Some((cl, off)) => {
-            let entry = shell.vol.read_raw_entry(cl, off).unwrap();
-            (cl, off, entry)
-        }
-        None => {
-            eprintln!("mv: cannot stat '{}': No such file or directory", src);
-            return;
+    let src_entry = match shell.vol.read_raw_entry(src_cluster, src_offset) {
+        Ok(e) => e,
+        Err(e) => {
+            eprintln!("mv: failed to read directory entry '{}': {}", src, e);
+            return 1;
         }
     };
-
-    let src_attr = src_entry[11];
-    let src_is_dir = (src_attr & 0x10) != 0;
-
+    if let Err(e) = check_modify(&src_entry, src, force) {
+        eprintln!("mv: {}", e);
+        return 1;
+    }
+    let src_is_dir = (src_entry[11] & 0x10) != 0;
     let hi = u16::from_le_bytes([src_entry[20], src_entry[21]]) as u32;
     let lo = u16::from_le_bytes([src_entry[26], src_entry[27]]) as u32;
     let src_start_cluster = (hi << 16) | lo;
 
-    let dest_entry = shell.vol.find_entry_in_directory(cwd, dest);
-
-    match dest_entry {
-        Some((dest_cl, dest_off)) => {
-            let entry = shell.vol.read_raw_entry(dest_cl, dest_off).unwrap();
-            let attr = entry[11];
-
-            if (attr & 0x10) == 0 {
-                eprintln!("mv: cannot overwrite '{}': not a directory", dest);
-                return;
-            }
-
-            let dest_dir_cluster = {
-                let hi = u16::from_le_bytes([entry[20], entry[21]]) as u32;
-                let lo = u16::from_le_bytes([entry[26], entry[27]]) as u32;
-                let cl = (hi << 16) | lo;
-                if cl == 0 { shell.vol.bpb.bpb_root_clus } else { cl }
+    // An existing directory receives SRC under its own name; anything else
+    // names the parent directory and the new name
+    let (dest_dir, dest_name) = match shell.vol.resolve_entry(cwd, dest) {
+        Some(target) if target.is_dir() => (target.first_cluster, src_name.to_string()),
+        _ => {
+            let (dir, name) = split_path(dest);
+            let Some(dir_cluster) = shell.vol.resolve_directory(cwd, dir) else {
+                eprintln!("mv: cannot move to '{}': No such directory", dest);
+                return 1;
             };
-
-            if src_is_dir && dest_dir_cluster == src_start_cluster {
-                eprintln!("mv: cannot move directory into itself");
-                return;
+            if name.is_empty() || name == "." || name == ".." {
+                eprintln!("mv: invalid destination name: {}", dest);
+                return 1;
             }
+            (dir_cluster, name.to_string())
+        }
+    };
 
-            let (free_cl, free_off) = match shell.vol.find_free_directory_entry(dest_dir_cluster) {
-                Some(v) => v,
-                None => {
-                    if let Some(new_cl) = shell.vol.append_cluster(dest_dir_cluster) {
-                        shell.vol.find_free_directory_entry(new_cl).unwrap()
-                    } else {
-                        eprintln!("mv: destination directory is full");
-                        return;
-                    }
-                }
-            };
-
-            let new_entry = src_entry.clone();
-            // Keep the SAME NAME as src
-            shell.vol.write_raw_entry(free_cl, free_off, &new_entry)
-                .expect("mv: failed to write destination entry");
-
-            shell.vol.mark_entry_deleted(src_cluster, src_offset)
-                .expect("mv: failed to delete old entry");
-
-            // Done
-            println!("moved '{}' into directory '{}'", src, dest);
+    if src_is_dir {
+        let inside = shell.vol.ancestors(dest_dir);
+        if inside.contains(&src_start_cluster) {
+            eprintln!("mv: cannot move directory '{}' into itself", src);
+            return 1;
+        }
+        // The shell keeps the path of the cwd, which would go stale
+        if shell.vol.ancestors(cwd).contains(&src_start_cluster) {
+            eprintln!("mv: cannot move '{}': it is the current directory or one of its parents", src);
+            return 1;
         }
+    }
 
-        None => {
-            let mut new_entry = src_entry.clone();
-            shell.vol.set_entry_name(&mut new_entry, dest);
+    // The entry to replace, if DST names something other than SRC itself
+    let mut replaced = None;
+    if let Some(existing) = shell.vol.find_entry_in_directory(dest_dir, &dest_name) {
+        // Short names carry no case, so DST naming SRC is never a rename
+        if existing == (src_cluster, src_offset) {
+            eprintln!("mv: source and destination are the same");
+            return 1;
+        }
+        let entry = match shell.vol.read_raw_entry(existing.0, existing.1) {
+            Ok(e) => e,
+            Err(e) => {
+                eprintln!("mv: failed to read directory entry '{}': {}", dest, e);
+                return 1;
+            }
+        };
+        if (entry[11] & 0x10) != 0 {
+            eprintln!("mv: cannot overwrite directory '{}'", dest);
+            return 1;
+        }
+        if src_is_dir {
+            eprintln!("mv: cannot overwrite non-directory '{}' with directory '{}'", dest, src);
+            return 1;
+        }
+        if shell.open_files.iter().any(|of| of.name.eq_ignore_ascii_case(&dest_name) && of.dir_cluster == dest_dir) {
+            eprintln!("mv: cannot overwrite open file '{}'", dest);
+            return 1;
+        }
+        if no_clobber || (interactive && !confirm(dest)) {
+            return 0;
+        }
+        if let Err(e) = check_modify(&entry, dest, force) {
+            eprintln!("mv: {}", e);
+            return 1;
+        }
+        let hi = u16::from_le_bytes([entry[20], entry[21]]) as u32;
+        let lo = u16::from_le_bytes([entry[26], entry[27]]) as u32;
+        replaced = Some((existing, (hi << 16) | lo));
+    }
 
-            shell.vol.write_raw_entry(src_cluster, src_offset, &new_entry)
-                .expect("mv: failed to update directory entry");
+    let mut new_entry = src_entry;
+    shell.vol.set_entry_name(&mut new_entry, &dest_name);
 
-            println!("renamed '{}' → '{}'", src, dest);
+    if replaced.is_none() && dest_dir == src_parent {
+        // A rename in place rewrites the entry; its long name no longer fits
+        if let Err(e) = shell.vol.delete_long_name(src_parent, src_cluster, src_offset) {
+            eprintln!("mv: failed to delete the old long name: {}", e);
+            return 1;
+        }
+        if let Err(e) = shell.vol.write_raw_entry(src_cluster, src_offset, &new_entry) {
+            eprintln!("mv: failed to update directory entry: {}", e);
+            return 1;
+        }
+    } else {
+        // Claim the destination slot, growing the directory and flushing that
+        // growth, before anything about SRC changes on disk
+        let (slot, old_chain) = match replaced {
+            Some((slot, old_chain)) => (slot, old_chain),
+            None => match shell.vol.free_entry_slot(dest_dir).and_then(|slot| shell.vol.flush_fat().map(|_| slot)) {
+                Ok(slot) => (slot, 0),
+                Err(e) => {
+                    eprintln!("mv: failed to make room in the destination directory: {}", e);
+                    return 1;
+                }
+            },
+        };
+        if let Err(e) = shell.vol.delete_entry(src_parent, src_cluster, src_offset) {
+            eprintln!("mv: failed to delete old entry: {}", e);
+            return 1;
+        }
+        if replaced.is_some() && let Err(e) = shell.vol.delete_long_name(dest_dir, slot.0, slot.1) {
+            eprintln!("mv: failed to delete the long name of '{}': {}", dest, e);
+            return 1;
+        }
+        if let Err(e) = shell.vol.write_raw_entry(slot.0, slot.1, &new_entry) {
+            eprintln!("mv: failed to write destination entry: {}", e);
+            return 1;
+        }
+        // The replaced file's clusters are unreferenced now
+        if let Err(e) = shell.vol.dealloc_chain(old_chain) {
+            eprintln!("mv: failed to free the clusters of '{}': {}", dest, e);
+            return 1;
+        }
+        if src_is_dir && dest_dir != src_parent && let Err(e) = set_parent(shell, src_start_cluster, dest_dir, root) {
+            eprintln!("mv: failed to update '..' in '{}': {}", dest, e);
+            return 1;
         }
     }
 
-    shell.vol.flush_fat().ok();
-}
\ No newline at end of file
+    if let Err(e) = shell.vol.flush_fat() {
+        eprintln!("mv: failed to flush FAT: {}", e);
+        return 1;
+    }
+    if dest_dir == src_parent {
+        writeln!(out, "renamed '{}' → '{}'", src, dest).ok();
+    } else {
+        writeln!(out, "moved '{}' → '{}'", src, dest).ok();
+    }
+    0
+}
+
+/// Asks on stderr whether to overwrite `path` and reads the answer from stdin.
+fn confirm(path: &str) -> bool {
+    // In a script or pipe the next line of stdin is a command, not an answer
+    if !std::io::stdin().is_terminal() {
+        eprintln!("mv: not overwriting '{}': -i can only ask at a terminal", path);
+        return false;
+    }
+    eprint!("mv: overwrite '{}'? ", path);
+    let mut answer = String::new();
+    if std::io::stdin().lock().read_line(&mut answer).is_err() {
+        return false;
+    }
+    matches!(answer.trim().to_ascii_lowercase().as_str(), "y" | "yes")
+}
+
+fn split_path(path: &str) -> (&str, &str) {
+    let trimmed = path.trim_end_matches('/');
+    match trimmed.rfind('/') {
+        Some(i) => (&trimmed[..i + 1], &trimmed[i + 1..]),
+        None => ("", trimmed),
+    }
+}
+
+/// Points the ".." entry of the directory at `dir_cluster` at `parent`,
+/// writing 0 when the parent is the root as FAT32 requires.
+fn set_parent(shell: &mut ShellCore, dir_cluster: u32, parent: u32, root: u32) -> std::io::Result<()> {
+    let Some((cluster, offset)) = shell.vol.find_entry_in_directory(dir_cluster, "..") else {
+        return Err(std::io::Error::new(std::io::ErrorKind::NotFound, "no '..' entry"));
+    };
+    let parent = if parent == root { 0 } else { parent };
+    let mut entry = shell.vol.read_raw_entry(cluster, offset)?;
+    entry[20..22].copy_from_slice(&((parent >> 16) as u16).to_le_bytes());
+    entry[26..28].copy_from_slice(&(parent as u16).to_le_bytes());
+    shell.vol.write_raw_entry(cluster, offset, &entry)
+}
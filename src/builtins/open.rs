@@ -1,24 +1,27 @@
+use crate::core::check_modify;
 use crate::models::{FileMode, OpenFile, ShellCore};
 
 
-pub fn open(filename: &str, mode: &str, shell: &mut ShellCore) {
-    if filename == "" {
+/// `open [-f] FILE MODE` opens a file in the current directory. Opening a
+/// read-only or system file for writing needs `-f`.
+pub fn open(filename: &str, mode: &str, force: bool, shell: &mut ShellCore) -> i32 {
+    if filename.is_empty() {
         eprintln!("open: missing filename");
-        return;
+        return 1;
     }
     if mode != "r" && mode != "w" && mode != "rw" && mode != "wr" && mode != "-r" && mode != "-w" && mode != "-rw" && mode != "-wr" {
         eprintln!("Invalid mode specified for open command. Use -r, -w, -rw, or -wr.");
-        return;
+        return 1;
     }
     for of in shell.open_files.iter() {
         if of.name.eq_ignore_ascii_case(filename) {
             eprintln!("open: file already open: {}", filename);
-            return;
+            return 1;
         }
     }
     if shell.open_files.len() >= 10 {
         eprintln!("open: maximum number of open files reached.");
-        return;
+        return 1;
     }
 
     let mut cwd_cluster = shell.cwd_cluster as usize;
@@ -44,8 +47,8 @@ pub fn open(filename: &str, mode: &str, shell: &mut ShellCore) {
                 }
 
                 let attr = buffer[offset + 11];
-                if attr == 0x0F {
-                    continue;
+                if (attr & 0x08) != 0 {
+                    continue; // LFN slot or volume label
                 }
 
                 let short_name = shell.vol.parse_short_name(&buffer[offset..offset + 11]);
@@ -55,11 +58,15 @@ pub fn open(filename: &str, mode: &str, shell: &mut ShellCore) {
                 let is_dir = (attr & 0x10) != 0;
                 if is_dir {
                     eprintln!("open: not a file: {}", filename);
-                    return;
+                    return 1;
                 }
-                if mode.contains('w') && (attr & 0x01 != 0) {
-                    eprintln!("open: file is read-only: {}", filename);
-                    return;
+                if mode.contains('w') {
+                    let mut raw = [0u8; 32];
+                    raw.copy_from_slice(&buffer[offset..offset + 32]);
+                    if let Err(e) = check_modify(&raw, filename, force) {
+                        eprintln!("open: {}", e);
+                        return 1;
+                    }
                 }
                 let high_cluster = u16::from_le_bytes([buffer[offset + 20], buffer[offset + 21]]);
                 let low_cluster = u16::from_le_bytes([buffer[offset + 26], buffer[offset + 27]]);
@@ -80,12 +87,13 @@ pub fn open(filename: &str, mode: &str, shell: &mut ShellCore) {
                 let open_file = OpenFile {
                     name: short_name,
                     file_descriptor,
-                    dir_cluster: cwd_cluster as u32,
+                    // The directory's first cluster, not the one the entry sits in
+                    dir_cluster: shell.cwd_cluster,
                     dir_cluster_path: shell.cwd_path.clone(),
                     start_cluster: new_cluster,
                     size: file_size,
                     offset: 0,
-                    mode: match mode {
+                    mode: match mode.trim_start_matches('-') {
                         "r" => FileMode::Read,
                         "w" => FileMode::Write,
                         "rw" | "wr" => FileMode::ReadWrite,
@@ -94,12 +102,12 @@ pub fn open(filename: &str, mode: &str, shell: &mut ShellCore) {
                 };
                 shell.open_files.push(open_file);
                 
-                return;
+                return 0;
             }
 
             
         }
-        let next = shell.vol.fat[cwd_cluster as usize] as usize;
+        let next = shell.vol.fat[cwd_cluster] as usize;
 
         if next >= 0x0FFFFFF8 {
             break;
@@ -107,4 +115,6 @@ pub fn open(filename: &str, mode: &str, shell: &mut ShellCore) {
 
         cwd_cluster = next;
     }
+    eprintln!("open: file not found: {}", filename);
+    1
 }
@@ -1,30 +1,32 @@
+use std::io::Write;
+
 use crate::models::{FileMode, ShellCore};
 
 
-pub fn read(fd: usize, size: usize, shell: &mut ShellCore) {
+pub fn read(fd: usize, size: usize, shell: &mut ShellCore, out: &mut dyn Write) -> i32 {
     let of = if let Some(of) = shell.open_files.iter_mut().find(|of| of.file_descriptor == fd) {
         of
     } else {
         eprintln!("read: file not open: {}", fd);
-        return;
+        return 1;
     };
 
     match of.mode {
         FileMode::Read | FileMode::ReadWrite => {}
         _ => {
             eprintln!("read: file not opened in read mode: {}", fd);
-            return;
+            return 1;
         }
     }
 
     if of.offset >= of.size {
-        return;
+        return 0;
     }
 
     let max_readable = of.size - of.offset;
     let bytes_to_read = std::cmp::min(size as u32, max_readable) as usize;
     if bytes_to_read == 0 {
-        return;
+        return 0;
     }
 
     let bytes_per_sector = shell.vol.bpb.bpb_byts_per_sec as usize;
@@ -32,7 +34,7 @@ pub fn read(fd: usize, size: usize, shell: &mut ShellCore) {
     let bytes_per_cluster = bytes_per_sector * sectors_per_cluster;
 
     let mut remaining = bytes_to_read;
-    let mut file_offset = of.offset as usize;
+    let file_offset = of.offset as usize;
 
     let mut cluster = of.start_cluster;
     let cluster_index = file_offset / bytes_per_cluster;
@@ -41,7 +43,7 @@ pub fn read(fd: usize, size: usize, shell: &mut ShellCore) {
     for _ in 0..cluster_index {
         let next = shell.vol.fat[cluster as usize];
         if next >= 0x0FFFFFF8 {
-            return;
+            return 1;
         }
         cluster = next;
     }
@@ -63,12 +65,9 @@ pub fn read(fd: usize, size: usize, shell: &mut ShellCore) {
         let take = remaining.min(available_in_cluster);
 
         let slice = &cluster_buf[inner_offset..inner_offset + take];
-        for &b in slice {
-            print!("{}", b as char);
-        }
+        out.write_all(slice).ok();
 
         remaining -= take;
-        file_offset += take;
 
         inner_offset = 0;
 
@@ -81,8 +80,8 @@ pub fn read(fd: usize, size: usize, shell: &mut ShellCore) {
         }
     }
 
-    use std::io::Write;
-    std::io::stdout().flush().ok();
+    out.flush().ok();
 
     of.offset += bytes_to_read as u32;
+    0
 }
@@ -1,60 +1,280 @@
+use std::collections::HashSet;
+
+use crate::core::check_modify;
 use crate::models::ShellCore;
 
-pub fn rm(filename: &str, shell: &mut ShellCore) {
-    if filename.is_empty() {
+/// Overwrite passes `rm --shred` makes when `--passes` is not given.
+const DEFAULT_SHRED_PASSES: u32 = 3;
+
+/// `rm [-r] [-f] [--shred [--passes N]] FILE...` removes each named file, so
+/// `rm *.LOG` clears out every match. `-r` removes directories and everything
+/// below them, depth first; `-f` ignores names that do not exist and removes
+/// read-only and system files too. `--shred`
+/// overwrites the data and the directory entries before freeing them.
+/// Returns 1 if anything could not be removed.
+pub fn rm(args: &[String], shell: &mut ShellCore) -> i32 {
+    let mut recursive = false;
+    let mut force = false;
+    let mut shred = false;
+    let mut passes = DEFAULT_SHRED_PASSES;
+    let mut filenames: Vec<&str> = Vec::new();
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        if arg == "--shred" {
+            shred = true;
+            continue;
+        }
+        if arg == "--passes" || arg.starts_with("--passes=") {
+            let value = match arg.strip_prefix("--passes=") {
+                Some(v) => Some(v),
+                None => iter.next().map(|v| v.as_str()),
+            };
+            match value.and_then(|v| v.parse::<u32>().ok()) {
+                Some(n) if n > 0 => passes = n,
+                _ => {
+                    eprintln!("rm: --passes needs a positive number");
+                    return 2;
+                }
+            }
+            continue;
+        }
+        match arg.strip_prefix('-') {
+            Some(flags) if !flags.is_empty() => {
+                for flag in flags.chars() {
+                    match flag {
+                        'r' | 'R' => recursive = true,
+                        'f' => force = true,
+                        other => {
+                            eprintln!("rm: unknown option: -{}", other);
+                            return 2;
+                        }
+                    }
+                }
+            }
+            _ => filenames.push(arg),
+        }
+    }
+
+    if filenames.is_empty() {
+        if force {
+            return 0;
+        }
         eprintln!("rm: missing file name :(");
-        return;
+        return 1;
     }
 
-    let parent_cluster = shell.cwd_cluster;
+    let shred = if shred { passes } else { 0 };
+    let mut status = 0;
+    for filename in filenames {
+        if rm_one(filename, recursive, force, shred, shell) != 0 {
+            status = 1;
+        }
+    }
+    status
+}
 
-    let (entry_cluster, entry_offset) =
-        match shell.vol.find_entry_in_directory(parent_cluster, filename) {
-            Some((cl, off)) => (cl, off),
-            None => {
-                eprintln!("rm: file not found: {}", filename);
-                return;
-            }
-        };
+/// `shred` is the number of overwrite passes, 0 for a plain delete.
+fn rm_one(path: &str, recursive: bool, force: bool, shred: u32, shell: &mut ShellCore) -> i32 {
+    let trimmed = path.trim_end_matches('/');
+    let (dir, filename) = match trimmed.rfind('/') {
+        Some(i) => (&trimmed[..i + 1], &trimmed[i + 1..]),
+        None => ("", trimmed),
+    };
+    let cwd = shell.cwd_cluster;
+    let found = match shell.vol.resolve_directory(cwd, dir) {
+        Some(parent) => shell.vol.find_entry_in_directory(parent, filename).map(|loc| (parent, loc)),
+        None => None,
+    };
+    let Some((parent_cluster, (entry_cluster, entry_offset))) = found else {
+        if force {
+            return 0;
+        }
+        eprintln!("rm: file not found: {}", path);
+        return 1;
+    };
 
     let raw = match shell.vol.read_raw_entry(entry_cluster, entry_offset) {
         Ok(r) => r,
         Err(_) => {
-            eprintln!("rm: failed to read directory entry: {}", filename);
-            return;
+            eprintln!("rm: failed to read directory entry: {}", path);
+            return 1;
         }
     };
 
+    if let Err(e) = check_modify(&raw, path, force) {
+        eprintln!("rm: {}", e);
+        return 1;
+    }
+
     let attr = raw[11];
     if (attr & 0x10) != 0 {
-        eprintln!("rm: {} is a directory", filename);
-        return;
+        if !recursive {
+            eprintln!("rm: {} is a directory", path);
+            return 1;
+        }
+        if filename == "." || filename == ".." {
+            eprintln!("rm: refusing to remove '.' or '..': {}", path);
+            return 1;
+        }
+
+        let hi = u16::from_le_bytes([raw[20], raw[21]]) as u32;
+        let lo = u16::from_le_bytes([raw[26], raw[27]]) as u32;
+        let dir_cluster = (hi << 16) | lo;
+        if shell.vol.ancestors(cwd).contains(&dir_cluster) {
+            eprintln!("rm: refusing to remove {}: it is the current directory or one of its parents", path);
+            return 1;
+        }
+
+        let mut visited = HashSet::new();
+        let emptied = remove_contents(shell, dir_cluster, trimmed, force, shred, &mut visited);
+        if let Err(e) = shell.vol.flush_fat() {
+            eprintln!("rm: failed to flush FAT: {}", e);
+            return 1;
+        }
+        if !emptied {
+            eprintln!("rm: {} not removed: it still holds entries that were skipped", path);
+            return 1;
+        }
     }
 
-    for of in shell.open_files.iter() {
-        if of.name.eq_ignore_ascii_case(filename) && of.dir_cluster == parent_cluster {
-            eprintln!("rm: cannot remove open file: {}", filename);
-            return;
+    if let Err(e) = unlink_entry(shell, parent_cluster, filename, force, shred) {
+        eprintln!("rm: {}", e);
+        return 1;
+    }
+    if let Err(e) = shell.vol.flush_fat() {
+        eprintln!("rm: failed to flush FAT: {}", e);
+        return 1;
+    }
+    0
+}
+
+/// Deletes everything inside a directory, depth first. Open files are
+/// skipped with a message; returns false if anything was left behind.
+fn remove_contents(
+    shell: &mut ShellCore,
+    dir_cluster: u32,
+    path: &str,
+    force: bool,
+    shred: u32,
+    visited: &mut HashSet<u32>,
+) -> bool {
+    // A directory that links back to an ancestor must not be walked twice
+    if !visited.insert(dir_cluster) {
+        return false;
+    }
+    if dir_cluster < 2 || dir_cluster > shell.vol.max_cluster() {
+        eprintln!("rm: {}: directory points at invalid cluster {}; nothing to remove inside it", path, dir_cluster);
+        return true;
+    }
+    let entries = match shell.vol.list_directory(dir_cluster) {
+        Ok(e) => e,
+        Err(e) => {
+            eprintln!("rm: failed to read directory {}: {}", path, e);
+            return false;
+        }
+    };
+
+    let mut emptied = true;
+    for entry in entries {
+        if entry.name == "." || entry.name == ".." {
+            continue;
+        }
+        let child_path = format!("{}/{}", path, entry.name);
+
+        if entry.is_dir() && !remove_contents(shell, entry.first_cluster, &child_path, force, shred, visited) {
+            emptied = false;
+            continue;
+        }
+        if !entry.is_dir() && is_open(shell, dir_cluster, &entry.name) {
+            eprintln!("rm: skipping open file: {}", child_path);
+            emptied = false;
+            continue;
+        }
+        if let Err(e) = unlink_entry(shell, dir_cluster, &entry.name, force, shred) {
+            eprintln!("rm: {}: {}", child_path, e);
+            emptied = false;
         }
     }
+    emptied
+}
+
+fn is_open(shell: &ShellCore, dir_cluster: u32, name: &str) -> bool {
+    shell
+        .open_files
+        .iter()
+        .any(|of| of.name.eq_ignore_ascii_case(name) && of.dir_cluster == dir_cluster)
+}
+
+/// Deletes a file, or a directory holding nothing but "." and "..", from the
+/// directory at `dir_cluster` and frees its clusters. Open files, read-only
+/// and system files and the current directory are refused.
+pub fn remove_entry(shell: &mut ShellCore, dir_cluster: u32, name: &str) -> Result<(), String> {
+    unlink_entry(shell, dir_cluster, name, false, 0)?;
+    shell.vol.flush_fat().map_err(|e| format!("failed to flush FAT: {}", e))
+}
+
+/// Does the work of `remove_entry` but leaves flushing the FAT to the caller,
+/// so removing a whole tree writes it once. `force` lets read-only and system
+/// files go. With `shred` passes the clusters are overwritten first and the
+/// entry and its long-name slots scrubbed.
+fn unlink_entry(shell: &mut ShellCore, dir_cluster: u32, name: &str, force: bool, shred: u32) -> Result<(), String> {
+    let (entry_cluster, entry_offset) = shell
+        .vol
+        .find_entry_in_directory(dir_cluster, name)
+        .ok_or_else(|| format!("file not found: {}", name))?;
+    let raw = shell
+        .vol
+        .read_raw_entry(entry_cluster, entry_offset)
+        .map_err(|_| format!("failed to read directory entry: {}", name))?;
+    check_modify(&raw, name, force).map_err(|e| e.to_string())?;
 
     let hi = u16::from_le_bytes([raw[20], raw[21]]) as u32;
     let lo = u16::from_le_bytes([raw[26], raw[27]]) as u32;
     let starting_cluster = (hi << 16) | lo;
 
-    if starting_cluster != 0 {
-        if let Err(e) = shell.vol.dealloc_chain(starting_cluster) {
-            eprintln!("rm: failed to deallocate clusters: {}", e);
-            return;
+    // A directory pointing at no valid cluster has nothing in it to check
+    let has_clusters = starting_cluster >= 2 && starting_cluster <= shell.vol.max_cluster();
+    if (raw[11] & 0x10) != 0 {
+        if starting_cluster == shell.cwd_cluster {
+            return Err(format!("cannot remove the current directory: {}", name));
+        }
+        if has_clusters {
+            let entries = shell
+                .vol
+                .list_directory(starting_cluster)
+                .map_err(|e| format!("failed to read directory {}: {}", name, e))?;
+            if entries.iter().any(|e| e.name != "." && e.name != "..") {
+                return Err(format!("directory not empty: {}", name));
+            }
         }
     }
 
-    if let Err(e) = shell.vol.mark_entry_deleted(entry_cluster, entry_offset) {
-        eprintln!("rm: failed to mark directory entry deleted: {}", e);
-        return;
+    if is_open(shell, dir_cluster, name) {
+        return Err(format!("cannot remove open file: {}", name));
     }
 
-    if let Err(e) = shell.vol.flush_fat() {
-        eprintln!("rm: failed to flush FAT: {}", e);
+    if shred > 0 {
+        let clusters = shell.vol.chain_clusters(starting_cluster);
+        shell
+            .vol
+            .shred_clusters(&clusters, shred)
+            .map_err(|e| format!("failed to overwrite {}: {}", name, e))?;
+    }
+    if starting_cluster != 0 {
+        shell
+            .vol
+            .dealloc_chain(starting_cluster)
+            .map_err(|e| format!("failed to deallocate clusters: {}", e))?;
+    }
+    if shred > 0 {
+        shell
+            .vol
+            .wipe_entry(dir_cluster, entry_cluster, entry_offset)
+            .map_err(|e| format!("failed to scrub directory entry: {}", e))
+    } else {
+        shell
+            .vol
+            .delete_entry(dir_cluster, entry_cluster, entry_offset)
+            .map_err(|e| format!("failed to mark directory entry deleted: {}", e))
     }
-}
\ No newline at end of file
+}
@@ -1,9 +1,14 @@
+use crate::core::check_modify;
 use crate::models::ShellCore;
 
-pub fn rmdir(dirname: &str, shell: &mut ShellCore) {
+/// `rmdir [-f] DIR` removes an empty directory. A read-only or system
+/// directory needs `-f`.
+pub fn rmdir(args: &[String], shell: &mut ShellCore) -> i32 {
+    let force = args.iter().any(|a| a == "-f");
+    let dirname = args.iter().find(|a| *a != "-f").map(|s| s.as_str()).unwrap_or("");
     if dirname.is_empty() {
         eprintln!("rmdir: missing directory name");
-        return;
+        return 1;
     }
 
     let parent_cluster = shell.cwd_cluster;
@@ -13,7 +18,7 @@ pub fn rmdir(dirname: &str, shell: &mut ShellCore) {
             Some(v) => v,
             None => {
                 eprintln!("rmdir: directory not found: {}", dirname);
-                return;
+                return 1;
             }
         };
 
@@ -21,18 +26,22 @@ pub fn rmdir(dirname: &str, shell: &mut ShellCore) {
         Ok(e) => e,
         Err(_) => {
             eprintln!("rmdir: failed to read directory entry '{}'", dirname);
-            return;
+            return 1;
         }
     };
 
     if (entry_raw[11] & 0x10) == 0 {
         eprintln!("rmdir: {} is not a directory", dirname);
-        return;
+        return 1;
     }
 
     if dirname == "." || dirname == ".." {
         eprintln!("rmdir: cannot remove '.' or '..'");
-        return;
+        return 1;
+    }
+    if let Err(e) = check_modify(&entry_raw, dirname, force) {
+        eprintln!("rmdir: {}", e);
+        return 1;
     }
 
     let hi = u16::from_le_bytes([entry_raw[20], entry_raw[21]]) as u32;
@@ -41,27 +50,29 @@ pub fn rmdir(dirname: &str, shell: &mut ShellCore) {
 
     if start_cluster == 0 {
         eprintln!("rmdir: invalid directory cluster");
-        return;
+        return 1;
     }
 
     if !dir_is_empty(shell, start_cluster) {
         eprintln!("rmdir: directory not empty: {}", dirname);
-        return;
+        return 1;
     }
 
     if let Err(e) = shell.vol.dealloc_chain(start_cluster) {
         eprintln!("rmdir: failed to deallocate clusters: {}", e);
-        return;
+        return 1;
     }
 
-    if let Err(e) = shell.vol.mark_entry_deleted(entry_cluster, entry_offset) {
+    if let Err(e) = shell.vol.delete_entry(parent_cluster, entry_cluster, entry_offset) {
         eprintln!("rmdir: failed to delete directory entry: {}", e);
-        return;
+        return 1;
     }
 
     if let Err(e) = shell.vol.flush_fat() {
         eprintln!("rmdir: failed to flush FAT: {}", e);
+        return 1;
     }
+    0
 }
 
 fn dir_is_empty(shell: &mut ShellCore, start: u32) -> bool {
@@ -0,0 +1,42 @@
+use std::io::Write;
+
+use crate::models::ShellCore;
+
+
+/// `set NAME=value` assigns a shell variable and `set` alone lists them.
+/// `set -e` stops a script at the first failing command, `set +e` turns that off.
+pub fn set(args: &[String], shell: &mut ShellCore, out: &mut dyn Write) -> i32 {
+    if args.is_empty() {
+        for (name, value) in shell.vars.iter() {
+            writeln!(out, "{}={}", name, value).ok();
+        }
+        writeln!(out, "errexit {}", if shell.errexit { "on" } else { "off" }).ok();
+        return 0;
+    }
+    for arg in args {
+        match arg.as_str() {
+            "-e" => shell.errexit = true,
+            "+e" => shell.errexit = false,
+            other => match other.split_once('=') {
+                Some((name, value)) if is_valid_name(name) => {
+                    shell.vars.insert(name.to_string(), value.to_string());
+                }
+                _ => {
+                    eprintln!("set: not an option or NAME=value: {}", other);
+                    return 1;
+                }
+            },
+        }
+    }
+    0
+}
+
+/// Variable names follow sh: a letter or underscore, then letters, digits or underscores.
+pub fn is_valid_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    match chars.next() {
+        Some(c) if c.is_ascii_alphabetic() || c == '_' => {}
+        _ => return false,
+    }
+    chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
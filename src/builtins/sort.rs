@@ -0,0 +1,60 @@
+use std::io::Write;
+
+use crate::builtins::filter_input;
+use crate::models::ShellCore;
+
+
+/// Sorts lines. Flags: -r (reverse), -n (numeric), -u (drop duplicates).
+pub fn sort(args: &[String], input: Option<&[u8]>, shell: &mut ShellCore, out: &mut dyn Write) -> i32 {
+    let mut reverse = false;
+    let mut numeric = false;
+    let mut unique = false;
+    let mut files: Vec<&str> = Vec::new();
+
+    for arg in args {
+        match arg.as_str() {
+            "-r" => reverse = true,
+            "-n" => numeric = true,
+            "-u" => unique = true,
+            flag if flag.starts_with('-') && flag.len() > 1 => {
+                eprintln!("sort: unknown option: {}", flag);
+                return 1;
+            }
+            file => files.push(file),
+        }
+    }
+
+    let data = match filter_input("sort", &files, input, shell) {
+        Some(d) => d,
+        None => return 1,
+    };
+    let text = String::from_utf8_lossy(&data);
+    let mut lines: Vec<&str> = text.lines().collect();
+
+    if numeric {
+        lines.sort_by(|a, b| leading_number(a).total_cmp(&leading_number(b)).then_with(|| a.cmp(b)));
+    } else {
+        lines.sort();
+    }
+    if unique {
+        lines.dedup();
+    }
+    if reverse {
+        lines.reverse();
+    }
+
+    for line in lines {
+        writeln!(out, "{}", line).ok();
+    }
+    0
+}
+
+fn leading_number(line: &str) -> f64 {
+    let trimmed = line.trim_start();
+    let end = trimmed
+        .char_indices()
+        .find(|&(i, c)| !(c.is_ascii_digit() || c == '.' || (i == 0 && c == '-')))
+        .map(|(i, _)| i)
+        .unwrap_or(trimmed.len());
+    trimmed[..end].parse().unwrap_or(0.0)
+}
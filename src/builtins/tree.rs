@@ -0,0 +1,91 @@
+use std::collections::HashSet;
+use std::io::Write;
+
+use crate::models::ShellCore;
+
+
+/// `tree [-L depth] [path]` draws the directory tree below `path` (the
+/// current directory by default) with box-drawing lines.
+pub fn tree(args: &[String], shell: &mut ShellCore, out: &mut dyn Write) -> i32 {
+    let mut max_depth: Option<usize> = None;
+    let mut path: Option<&str> = None;
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "-L" => match iter.next().and_then(|d| d.parse().ok()) {
+                Some(depth) if depth > 0 => max_depth = Some(depth),
+                _ => {
+                    eprintln!("tree: -L needs a depth of at least 1");
+                    return 2;
+                }
+            },
+            other if other.starts_with('-') => {
+                eprintln!("tree: unknown option: {}", other);
+                return 2;
+            }
+            other if path.is_none() => path = Some(other),
+            _ => {
+                eprintln!("tree: too many arguments");
+                return 2;
+            }
+        }
+    }
+
+    let path = path.unwrap_or(".");
+    let cwd = shell.cwd_cluster;
+    let Some(cluster) = shell.vol.resolve_directory(cwd, path) else {
+        eprintln!("tree: {}: no such directory", path);
+        return 1;
+    };
+
+    if cluster < 2 || cluster > shell.vol.max_cluster() {
+        eprintln!("tree: {}: directory points at invalid cluster {}", path, cluster);
+        return 1;
+    }
+
+    writeln!(out, "{}", path).ok();
+    let mut walk = TreeWalk { max_depth, directories: 0, files: 0, visited: HashSet::from([cluster]) };
+    if let Err(e) = walk.draw(shell, cluster, "", 1, out) {
+        eprintln!("tree: failed to read directory: {}", e);
+        return 1;
+    }
+    writeln!(out, "\n{} directories, {} files", walk.directories, walk.files).ok();
+    0
+}
+
+struct TreeWalk {
+    max_depth: Option<usize>,
+    directories: u32,
+    files: u32,
+    visited: HashSet<u32>, // guards against directories that link back to an ancestor
+}
+
+impl TreeWalk {
+    fn draw(&mut self, shell: &mut ShellCore, cluster: u32, prefix: &str, depth: usize, out: &mut dyn Write) -> std::io::Result<()> {
+        let mut entries = shell.vol.list_directory(cluster)?;
+        entries.retain(|e| e.name != "." && e.name != "..");
+        entries.sort_by(|a, b| a.name.cmp(&b.name));
+
+        let count = entries.len();
+        for (i, entry) in entries.into_iter().enumerate() {
+            let last = i + 1 == count;
+            writeln!(out, "{}{}{}", prefix, if last { "└── " } else { "├── " }, entry.name).ok();
+
+            if !entry.is_dir() {
+                self.files += 1;
+                continue;
+            }
+            self.directories += 1;
+            if self.max_depth.is_some_and(|max| depth >= max) || !self.visited.insert(entry.first_cluster) {
+                continue;
+            }
+            if entry.first_cluster < 2 || entry.first_cluster > shell.vol.max_cluster() {
+                eprintln!("tree: {}: directory points at invalid cluster {}; skipping it", entry.name, entry.first_cluster);
+                continue;
+            }
+            let child_prefix = format!("{}{}", prefix, if last { "    " } else { "│   " });
+            self.draw(shell, entry.first_cluster, &child_prefix, depth + 1, out)?;
+        }
+        Ok(())
+    }
+}
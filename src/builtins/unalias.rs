@@ -0,0 +1,17 @@
+use crate::models::ShellCore;
+
+
+pub fn unalias(names: &[&str], shell: &mut ShellCore) -> i32 {
+    if names.is_empty() {
+        eprintln!("unalias: missing alias name");
+        return 1;
+    }
+    let mut status = 0;
+    for name in names {
+        if shell.aliases.remove(*name).is_none() {
+            eprintln!("unalias: {}: not found", name);
+            status = 1;
+        }
+    }
+    status
+}
@@ -0,0 +1,128 @@
+use std::io::Write;
+
+use crate::builtins::lsdel::{Deleted, deleted_entries, free_run};
+use crate::core::short_name;
+use crate::models::ShellCore;
+
+/// `undelete [dir/]ENTRY NEWNAME` brings back a deleted entry under a new
+/// name. ENTRY is the number `lsdel` shows or the name with its '?'. The
+/// chain is rebuilt on the assumption the file was stored contiguously; if
+/// some of those clusters have been reused since, the file is cut short
+/// before the first of them rather than sharing it.
+pub fn undelete(args: &[String], shell: &mut ShellCore, out: &mut dyn Write) -> i32 {
+    let [target, new_name] = args else {
+        eprintln!("undelete: usage: undelete [dir/]ENTRY NEWNAME");
+        return 2;
+    };
+    if new_name.is_empty() || new_name.contains('/') || new_name == "." || new_name == ".." {
+        eprintln!("undelete: invalid name: {}", new_name);
+        return 1;
+    }
+
+    let (dir_path, entry) = match target.rfind('/') {
+        Some(i) => (&target[..i + 1], &target[i + 1..]),
+        None => ("", target.as_str()),
+    };
+    let cwd = shell.cwd_cluster;
+    let Some(dir) = shell.vol.resolve_directory(cwd, dir_path) else {
+        eprintln!("undelete: {}: no such directory", dir_path);
+        return 1;
+    };
+    let deleted = match deleted_entries(shell, dir) {
+        Ok(d) => d,
+        Err(e) => {
+            eprintln!("undelete: failed to read directory: {}", e);
+            return 1;
+        }
+    };
+    let d = match pick(shell, &deleted, entry) {
+        Ok(d) => d,
+        Err(e) => {
+            eprintln!("undelete: {}", e);
+            return 1;
+        }
+    };
+    if shell.vol.find_entry_in_directory(dir, new_name).is_some() {
+        eprintln!("undelete: {} already exists", new_name);
+        return 1;
+    }
+
+    let bytes_per_cluster = shell.vol.bpb.bpb_byts_per_sec as u32 * shell.vol.bpb.bpb_sec_per_clus as u32;
+    let first = d.first_cluster();
+    let needed = d.clusters_needed(bytes_per_cluster);
+    let run = free_run(shell, first, needed);
+    let mut size = d.size();
+    if needed > 0 && run == 0 {
+        eprintln!("undelete: the clusters of {} have been reused; nothing left to recover", d.name(shell));
+        return 1;
+    }
+    if run < needed {
+        size = run * bytes_per_cluster;
+        eprintln!(
+            "undelete: warning: {} of {} clusters have been reused; {} is cut to {} bytes",
+            needed - run, needed, new_name, size
+        );
+    }
+
+    // Link the chain before the entry comes back, so an interruption leaves
+    // lost clusters rather than an entry pointing at free ones
+    for cluster in first..first + run {
+        shell.vol.fat[cluster as usize] = if cluster + 1 < first + run { cluster + 1 } else { 0x0FFFFFF8 };
+    }
+    if let Err(e) = shell.vol.flush_fat() {
+        eprintln!("undelete: failed to flush FAT: {}", e);
+        return 1;
+    }
+
+    if d.is_dir() && !dot_entry_matches(shell, first) {
+        eprintln!("undelete: warning: cluster {} no longer starts with a '.' entry; the directory may be damaged", first);
+    }
+
+    let mut raw = d.raw;
+    raw[0..11].copy_from_slice(&short_name(new_name));
+    raw[28..32].copy_from_slice(&size.to_le_bytes());
+    if let Err(e) = shell.vol.write_raw_entry(d.cluster, d.offset, &raw) {
+        eprintln!("undelete: failed to write directory entry: {}", e);
+        return 1;
+    }
+    writeln!(out, "Recovered {} as {} ({} bytes)", d.name(shell), new_name, size).ok();
+    0
+}
+
+/// Finds the entry a user named by its `lsdel` number or by its name, where
+/// the first character may be '?' or the original letter.
+fn pick<'a>(shell: &ShellCore, deleted: &'a [Deleted], entry: &str) -> Result<&'a Deleted, String> {
+    if let Ok(index) = entry.parse::<usize>() {
+        return deleted
+            .iter()
+            .find(|d| d.index == index)
+            .ok_or_else(|| format!("no deleted entry numbered {}", index));
+    }
+
+    let matches: Vec<&Deleted> = deleted
+        .iter()
+        .filter(|d| {
+            let name = d.name(shell);
+            name.len() == entry.len() && entry.is_char_boundary(1) && name[1..].eq_ignore_ascii_case(&entry[1..])
+        })
+        .collect();
+    match matches[..] {
+        [d] => Ok(d),
+        [] => Err(format!("no deleted entry matches {}", entry)),
+        _ => {
+            let numbers: Vec<String> = matches.iter().map(|d| d.index.to_string()).collect();
+            Err(format!("{} matches entries {}; pick one by number", entry, numbers.join(", ")))
+        }
+    }
+}
+
+fn dot_entry_matches(shell: &mut ShellCore, cluster: u32) -> bool {
+    match shell.vol.read_raw_entry(cluster, 0) {
+        Ok(raw) => {
+            let hi = u16::from_le_bytes([raw[20], raw[21]]) as u32;
+            let lo = u16::from_le_bytes([raw[26], raw[27]]) as u32;
+            &raw[0..11] == b".          " && (hi << 16) | lo == cluster
+        }
+        Err(_) => false,
+    }
+}
@@ -0,0 +1,13 @@
+use crate::models::ShellCore;
+
+
+pub fn unset(names: &[&str], shell: &mut ShellCore) -> i32 {
+    if names.is_empty() {
+        eprintln!("unset: missing variable name");
+        return 1;
+    }
+    for name in names {
+        shell.vars.remove(*name);
+    }
+    0
+}
@@ -0,0 +1,55 @@
+use std::io::Write;
+
+use crate::builtins::filter_input;
+use crate::models::ShellCore;
+
+
+/// Counts lines, words and bytes. With no flags all three are printed.
+pub fn wc(args: &[String], input: Option<&[u8]>, shell: &mut ShellCore, out: &mut dyn Write) -> i32 {
+    let mut show_lines = false;
+    let mut show_words = false;
+    let mut show_bytes = false;
+    let mut files: Vec<&str> = Vec::new();
+
+    for arg in args {
+        match arg.as_str() {
+            "-l" => show_lines = true,
+            "-w" => show_words = true,
+            "-c" => show_bytes = true,
+            flag if flag.starts_with('-') && flag.len() > 1 => {
+                eprintln!("wc: unknown option: {}", flag);
+                return 1;
+            }
+            file => files.push(file),
+        }
+    }
+    if !show_lines && !show_words && !show_bytes {
+        show_lines = true;
+        show_words = true;
+        show_bytes = true;
+    }
+
+    let data = match filter_input("wc", &files, input, shell) {
+        Some(d) => d,
+        None => return 1,
+    };
+
+    let lines = data.iter().filter(|&&b| b == b'\n').count();
+    let words = String::from_utf8_lossy(&data).split_whitespace().count();
+
+    let mut fields = Vec::new();
+    if show_lines {
+        fields.push(format!("{:>7}", lines));
+    }
+    if show_words {
+        fields.push(format!("{:>7}", words));
+    }
+    if show_bytes {
+        fields.push(format!("{:>7}", data.len()));
+    }
+    if files.len() == 1 {
+        fields.push(files[0].to_string());
+    }
+    writeln!(out, "{}", fields.join(" ")).ok();
+    0
+}
@@ -0,0 +1,105 @@
+use std::collections::HashSet;
+use std::io::Write;
+
+use crate::models::{ShellCore, Volume};
+
+/// `wipe-free [--passes N]` overwrites every free cluster, then scrubs the
+/// deleted entries and stray long-name slots left in every directory, so
+/// nothing removed earlier can be carved back out of the image.
+pub fn wipe_free(args: &[String], shell: &mut ShellCore, out: &mut dyn Write) -> i32 {
+    let mut passes = 1;
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        let value = match arg.as_str() {
+            "--passes" => iter.next().map(|v| v.as_str()),
+            other if other.starts_with("--passes=") => Some(&other["--passes=".len()..]),
+            other => {
+                eprintln!("wipe-free: unknown argument: {}", other);
+                return 2;
+            }
+        };
+        match value.and_then(|v| v.parse::<u32>().ok()) {
+            Some(n) if n > 0 => passes = n,
+            _ => {
+                eprintln!("wipe-free: --passes needs a positive number");
+                return 2;
+            }
+        }
+    }
+
+    let max_cluster = shell.vol.max_cluster();
+    let free: Vec<u32> = (2..=max_cluster).filter(|&c| shell.vol.fat[c as usize] & 0x0FFFFFFF == 0).collect();
+    if let Err(e) = shell.vol.shred_clusters(&free, passes) {
+        eprintln!("wipe-free: failed to overwrite free clusters: {}", e);
+        return 1;
+    }
+
+    let root = shell.vol.bpb.bpb_root_clus;
+    let mut pending = vec![root];
+    let mut visited = HashSet::new();
+    let mut scrubbed = 0;
+    while let Some(dir) = pending.pop() {
+        // A directory that links back to an ancestor is only scrubbed once
+        if !visited.insert(dir) {
+            continue;
+        }
+        match scrub_directory(shell, dir) {
+            Ok(count) => scrubbed += count,
+            Err(e) => {
+                eprintln!("wipe-free: failed to scrub directory at cluster {}: {}", dir, e);
+                return 1;
+            }
+        }
+        let children = match shell.vol.list_directory(dir) {
+            Ok(c) => c,
+            Err(e) => {
+                eprintln!("wipe-free: failed to read directory at cluster {}: {}", dir, e);
+                return 1;
+            }
+        };
+        pending.extend(
+            children
+                .iter()
+                .filter(|e| e.is_dir() && e.name != "." && e.name != ".." && e.first_cluster >= 2)
+                .map(|e| e.first_cluster),
+        );
+    }
+
+    writeln!(out, "Wiped {} free clusters and {} directory entries", free.len(), scrubbed).ok();
+    0
+}
+
+/// Zeroes every deleted entry in a directory that still holds data, and
+/// every long-name slot that does not lead up to a live entry, keeping the
+/// deleted mark. Returns how many slots were scrubbed.
+fn scrub_directory(shell: &mut ShellCore, dir: u32) -> std::io::Result<usize> {
+    let slots = shell.vol.raw_entries(dir)?;
+    let mut wiped = [0u8; 32];
+    wiped[0] = 0xE5;
+
+    let mut scrub = Vec::new();
+    // Long-name slots seen since the last short entry
+    let mut run: Vec<usize> = Vec::new();
+    for (i, (_, _, raw)) in slots.iter().enumerate() {
+        if raw[11] == 0x0F && raw[0] != 0xE5 {
+            run.push(i);
+            continue;
+        }
+        let deleted = raw[0] == 0xE5;
+        let owns_run = !deleted && run.iter().all(|&r| slots[r].2[13] == Volume::lfn_checksum(&raw[0..11]));
+        if !owns_run {
+            scrub.append(&mut run);
+        }
+        run.clear();
+        if deleted && raw[1..].iter().any(|&b| b != 0) {
+            scrub.push(i);
+        }
+    }
+    scrub.append(&mut run);
+
+    for &i in &scrub {
+        let (cluster, offset, _) = slots[i];
+        shell.vol.write_raw_entry(cluster, offset, &wiped)?;
+    }
+    Ok(scrub.len())
+}
@@ -1,6 +1,6 @@
 use crate::models::{FileMode, ShellCore};
 
-pub fn write(fd: u32, data: &str, shell: &mut ShellCore) {
+pub fn write(fd: u32, data: &[u8], shell: &mut ShellCore) -> i32 {
     let of = if let Some(of) = shell
         .open_files
         .iter_mut()
@@ -9,21 +9,21 @@ pub fn write(fd: u32, data: &str, shell: &mut ShellCore) {
         of
     } else {
         eprintln!("write: file not open: {}", fd);
-        return;
+        return 1;
     };
 
     match of.mode {
         FileMode::Write | FileMode::ReadWrite => {}
         _ => {
             eprintln!("write: file not opened in write mode: {}", fd);
-            return;
+            return 1;
         }
     }
 
-    let data_bytes = data.as_bytes();
+    let data_bytes = data;
     let mut remaining = data_bytes.len();
     if remaining == 0 {
-        return;
+        return 0;
     }
 
     let bytes_per_sector = shell.vol.bpb.bpb_byts_per_sec as usize;
@@ -37,7 +37,7 @@ pub fn write(fd: u32, data: &str, shell: &mut ShellCore) {
             }
             None => {
                 eprintln!("write: failed to allocate first cluster");
-                return;
+                return 1;
             }
         }
     }
@@ -59,7 +59,7 @@ pub fn write(fd: u32, data: &str, shell: &mut ShellCore) {
                 }
                 None => {
                     eprintln!("write: failed to extend cluster chain");
-                    return;
+                    return 1;
                 }
             }
         } else {
@@ -80,7 +80,7 @@ pub fn write(fd: u32, data: &str, shell: &mut ShellCore) {
                 &mut cluster_buf[sec * bytes_per_sector..(sec + 1) * bytes_per_sector],
             ) {
                 eprintln!("write: failed to read cluster sector: {}", e);
-                return;
+                return 1;
             }
         }
 
@@ -101,10 +101,10 @@ pub fn write(fd: u32, data: &str, shell: &mut ShellCore) {
             if let Err(e) =
                 shell
                     .vol
-                    .write_sector(sector_num, &cluster_buf[start..end])
+                    .write_data_sector(sector_num, &cluster_buf[start..end])
             {
                 eprintln!("write: failed to write cluster sector: {}", e);
-                return;
+                return 1;
             }
         }
 
@@ -125,7 +125,7 @@ pub fn write(fd: u32, data: &str, shell: &mut ShellCore) {
                     }
                     None => {
                         eprintln!("write: failed to allocate new cluster");
-                        return;
+                        return 1;
                     }
                 }
             } else {
@@ -139,6 +139,7 @@ pub fn write(fd: u32, data: &str, shell: &mut ShellCore) {
         of.size = file_offset as u32;
     }
 
+    let mut status = 0;
     if let Err(e) = shell.vol.update_dir_entry(
         of.dir_cluster,      // parent directory
         &of.name,            // short filename
@@ -146,10 +147,12 @@ pub fn write(fd: u32, data: &str, shell: &mut ShellCore) {
         of.size,             // updated size
     ) {
         eprintln!("write: failed to update directory entry: {}", e);
+        status = 1;
     }
 
     if let Err(e) = shell.vol.flush_fat() {
         eprintln!("write: failed to flush FAT to disk: {}", e);
-        return;
+        return 1;
     }
+    status
 }
\ No newline at end of file
@@ -1,34 +1,111 @@
+use std::io::Write;
+
 use crate::{
     builtins::*,
     models::ShellCore};
 
+pub const BUILTINS: &[&str] = &[
+    "info", "exit", "cd", "ls", "open",
+    "close", "lsof", "lseek", "read",
+    "mkdir", "creat", "write", "mv", "rm", "rmdir",
+    "cat", "grep", "wc", "sort", "head", "set", "echo",
+    "unset", "alias", "unalias", "fsck", "label", "df", "tree", "du", "find", "cp",
+    "lsdel", "undelete", "wipe-free", "attrib", "commit", "discard", "diff-overlay",
+];
+
 pub fn is_built_in(command: &str) -> bool {
+    BUILTINS.contains(&command)
+}
+
+/// Runs a builtin and returns its exit status (0 on success). `input` holds the
+/// output of the previous pipeline stage (if any) and everything the builtin
+/// prints goes to `out`.
+pub fn execute_built_in(command: &str, shell: &mut ShellCore, args: &[String], input: Option<&[u8]>, out: &mut dyn Write) -> i32 {
+    let status = run_built_in(command, shell, args, input, out);
+
+    // Everything the command changed reaches the image together, or not at all
+    if let Err(e) = shell.vol.commit_journal() {
+        eprintln!("{}: failed to commit the journal: {}", command, e);
+        return 1;
+    }
+    status
+}
+
+/// Runs a builtin like `execute_built_in` but leaves its changes staged in
+/// the journal, for builtins such as `find -exec` that run others as part of
+/// one command.
+pub fn run_built_in(command: &str, shell: &mut ShellCore, args: &[String], input: Option<&[u8]>, out: &mut dyn Write) -> i32 {
+    // With an overlay the image itself is only written by `commit`
+    if shell.read_only && shell.vol.overlay.is_none() && modifies_image(command, args) {
+        eprintln!("{}: the image is mounted read-only", command);
+        return 1;
+    }
+    let operands: Vec<&str> = args.iter().map(|s| s.as_str()).collect();
     match command {
-        "info" | "exit" | "cd" | "ls" | "open" | 
-        "close" | "lsof" | "lseek" | "read" | 
-        "mkdir" | "creat" | "write" | "mv" | "rm" | "rmdir" => true,
-        _ => false,
+        "info" => info::info(&shell.vol.bpb, shell.read_only, out),
+        "exit" => exit::exit(args.first().and_then(|s| s.parse().ok()).unwrap_or(shell.last_status)),
+        "cd" => cd::cd(args.first().map(|s| s.as_str()).unwrap_or(""), shell),
+        "ls" => {
+            let flag = |c| args.iter().any(|a| a.starts_with('-') && a.contains(c));
+            ls::ls(flag('l'), flag('a'), shell, out)
+        }
+        "open" => {
+            let force = args.iter().any(|a| a == "-f");
+            let mut rest = args.iter().filter(|a| *a != "-f").map(|s| s.as_str());
+            open::open(rest.next().unwrap_or(""), rest.next().unwrap_or("r"), force, shell)
+        }
+        "close" => close::close(args.first().and_then(|s| s.parse().ok()).unwrap_or(0), shell),
+        "lsof" => lsof::lsof(shell, out),
+        "lseek" => lseek::lseek(args.first().and_then(|s| s.parse().ok()).unwrap_or(0), args.get(1).and_then(|s| s.parse().ok()).unwrap_or(0), shell),
+        "read" => read::read(args.first().and_then(|s| s.parse().ok()).unwrap_or(0), args.get(1).and_then(|s| s.parse().ok()).unwrap_or(0), shell, out),
+        "mkdir" => mkdir::mkdir(args.first().map(|s| s.as_str()).unwrap_or(""), shell),
+        "creat" => creat::creat(args.first().map(|s| s.as_str()).unwrap_or(""), shell),
+        "write" => write::write(args.first().and_then(|s| s.parse().ok()).unwrap_or(0), args.get(1).map(|s| s.as_bytes()).or(input).unwrap_or(b""), shell),
+        "mv" => mv::mv(args, shell, out),
+        "rm" => rm::rm(args, shell),
+        "rmdir" => rmdir::rmdir(args, shell),
+        "cat" => cat::cat(&operands, input, shell, out),
+        "grep" => grep::grep(args, input, shell, out),
+        "wc" => wc::wc(args, input, shell, out),
+        "sort" => sort::sort(args, input, shell, out),
+        "head" => head::head(args, input, shell, out),
+        "set" => set::set(args, shell, out),
+        "echo" => echo::echo(args, out),
+        "unset" => unset::unset(&operands, shell),
+        "alias" => alias::alias(args, shell, out),
+        "unalias" => unalias::unalias(&operands, shell),
+        "fsck" => fsck::fsck(args, shell, out),
+        "label" => label::label(args, shell, out),
+        "df" => df::df(args.iter().any(|a| a == "-h"), shell, out),
+        "tree" => tree::tree(args, shell, out),
+        "du" => du::du(args, shell, out),
+        "find" => find::find(args, shell, out),
+        "cp" => cp::cp(args, shell),
+        "lsdel" => lsdel::lsdel(args, shell, out),
+        "undelete" => undelete::undelete(args, shell, out),
+        "wipe-free" => wipe_free::wipe_free(args, shell, out),
+        "attrib" => attrib::attrib(args, shell, out),
+        "commit" => commit::commit(args, shell, out),
+        "discard" => discard::discard(args, shell, out),
+        "diff-overlay" => diff_overlay::diff_overlay(args, shell, out),
+        _ => {
+            eprintln!("Unknown built-in command: {}", command);
+            127
+        }
     }
 }
 
-pub fn execute_built_in(command: &str, shell: &mut ShellCore, args: &[String]) {
+/// Whether running `command` with `args` would write to the image. Commands
+/// that only sometimes write are judged by their arguments.
+fn modifies_image(command: &str, args: &[String]) -> bool {
+    let has = |flag: &str| args.iter().any(|a| a == flag);
     match command {
-        "info" => info::info(&shell.vol.bpb),
-        "exit" => exit::exit(),
-        "cd" => cd::cd(args.get(0).map(|s| s.as_str()).unwrap_or(""), shell),
-        "ls" => ls::ls(shell),
-        "open" => open::open(args.get(0).map(|s| s.as_str()).unwrap_or(""),
-         args.get(1).map(|s| s.as_str()).unwrap_or("r"), shell),
-        "close" => close::close(args.get(0).and_then(|s| s.parse().ok()).unwrap_or(0), shell),
-        "lsof" => lsof::lsof(shell),
-        "lseek" => lseek::lseek(args.get(0).and_then(|s| s.parse().ok()).unwrap_or(0), args.get(1).and_then(|s| s.parse().ok()).unwrap_or(0), shell),
-        "read" => read::read(args.get(0).and_then(|s| s.parse().ok()).unwrap_or(0), args.get(1).and_then(|s| s.parse().ok()).unwrap_or(0), shell),  
-        "mkdir" => mkdir::mkdir(args.get(0).map(|s| s.as_str()).unwrap_or(""), shell),
-        "creat" => creat::creat(args.get(0).map(|s| s.as_str()).unwrap_or(""), shell),
-        "write" => write::write(args.get(0).and_then(|s| s.parse().ok()).unwrap_or(0), args.get(1).map(|s| s.as_str()).unwrap_or(""), shell),
-        "mv" => mv::mv(shell, args.get(0).map(|s| s.as_str()).unwrap_or(""), args.get(1).map(|s| s.as_str()).unwrap_or("")),
-        "rm" => rm::rm(args.get(0).map(|s| s.as_str()).unwrap_or(""), shell),
-        "rmdir" => rmdir::rmdir(args.get(0).map(|s| s.as_str()).unwrap_or(""), shell),
-        _ => eprintln!("Unknown built-in command: {}", command),
+        "mkdir" | "creat" | "write" | "mv" | "rm" | "rmdir" | "cp" | "undelete" | "wipe-free" => true,
+        "open" => args.iter().filter(|a| *a != "-f").nth(1).is_some_and(|mode| mode.contains('w')),
+        "label" => !args.is_empty(),
+        "fsck" => has("--repair") && !has("--dry-run"),
+        "attrib" => args.iter().any(|a| a.len() > 1 && (a.starts_with('+') || (a.starts_with('-') && a != "-R"))),
+        "find" => has("-delete"),
+        _ => false,
     }
-}
\ No newline at end of file
+}
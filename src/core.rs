@@ -1,9 +1,9 @@
-use std::{fs::File, io::{Read, Seek, SeekFrom, Write}};
+use std::{collections::{BTreeMap, HashSet}, fs::File, io::{Read, Seek, SeekFrom, Write}, time::{SystemTime, UNIX_EPOCH}};
 
-use crate::models::{Volume, ShellCore, BootSector};
+use crate::models::{BootSector, DirEntry, PermissionError, ShellCore, Volume};
 
 impl ShellCore {
-    pub fn new(mut image: File) -> Self {
+    pub fn new(mut image: File, read_only: bool) -> Self {
         let bpb = BootSector::new(&mut image);
         let vol = Volume::new(image, bpb);
         ShellCore {
@@ -11,6 +11,11 @@ impl ShellCore {
             cwd_cluster: bpb.bpb_root_clus,
             cwd_path: String::from("/"),
             open_files: Vec::new(),
+            last_status: 0,
+            errexit: false,
+            vars: BTreeMap::from([(String::from("HOME"), String::from("/"))]),
+            aliases: BTreeMap::new(),
+            read_only,
         }
     }
 }
@@ -40,6 +45,8 @@ impl Volume {
             first_fat_sector,
             first_data_sector,
             fat,
+            journal: None,
+            overlay: None,
         }
     }
 
@@ -111,7 +118,8 @@ impl Volume {
                     if first == 0x00 {
                         return None; // end-of-dir
                     }
-                    if first == 0xE5 || entry[11] == 0x0F {
+                    // LFN slots and the volume label are never files
+                    if first == 0xE5 || (entry[11] & 0x08) != 0 {
                         continue;
                     }
 
@@ -130,6 +138,325 @@ impl Volume {
         }
     }
 
+    /// Lists the live entries of a directory, including "." and "..".
+    /// Deleted entries, LFN slots and the volume label are skipped.
+    pub fn list_directory(&mut self, start_cluster: u32) -> std::io::Result<Vec<DirEntry>> {
+        let bytes_per_sector = self.bpb.bpb_byts_per_sec as usize;
+        let sectors = self.bpb.bpb_sec_per_clus as usize;
+        let bytes_per_cluster = bytes_per_sector * sectors;
+
+        let mut entries = Vec::new();
+        let mut cluster = start_cluster;
+        let mut buffer = vec![0u8; bytes_per_cluster];
+        // Long-name slots seen since the last short entry, in disk order
+        let mut lfn_slots: Vec<Vec<u16>> = Vec::new();
+        let mut lfn_sum = 0u8;
+
+        'chain: loop {
+            let first_sector = self.get_first_sector_of_cluster(cluster);
+            for s in 0..sectors {
+                self.read_sector(
+                    first_sector + s as u32,
+                    &mut buffer[s * bytes_per_sector..(s + 1) * bytes_per_sector],
+                )?;
+            }
+
+            for offset in (0..bytes_per_cluster).step_by(32) {
+                let entry = &buffer[offset..offset + 32];
+                if entry[0] == 0x00 {
+                    break 'chain;
+                }
+                if entry[11] == 0x0F && entry[0] != 0xE5 {
+                    if (entry[0] & 0x40) != 0 {
+                        lfn_slots.clear();
+                        lfn_sum = entry[13];
+                    }
+                    lfn_slots.push(Self::lfn_chars(entry));
+                    continue;
+                }
+                if entry[0] == 0xE5 || (entry[11] & 0x08) != 0 {
+                    lfn_slots.clear();
+                    continue;
+                }
+
+                // The slots belong to this entry only if their checksum matches its 8.3 name
+                let long_name = if !lfn_slots.is_empty() && lfn_sum == Self::lfn_checksum(&entry[0..11]) {
+                    let units: Vec<u16> = lfn_slots
+                        .iter()
+                        .rev()
+                        .flatten()
+                        .copied()
+                        .take_while(|&u| u != 0x0000)
+                        .collect();
+                    Some(String::from_utf16_lossy(&units))
+                } else {
+                    None
+                };
+                lfn_slots.clear();
+
+                let hi = u16::from_le_bytes([entry[20], entry[21]]) as u32;
+                let lo = u16::from_le_bytes([entry[26], entry[27]]) as u32;
+                entries.push(DirEntry {
+                    name: self.parse_short_name(&entry[0..11]),
+                    long_name,
+                    attr: entry[11],
+                    first_cluster: (hi << 16) | lo,
+                    size: u32::from_le_bytes([entry[28], entry[29], entry[30], entry[31]]),
+                    modified: fat_to_unix(
+                        u16::from_le_bytes([entry[24], entry[25]]),
+                        u16::from_le_bytes([entry[22], entry[23]]),
+                    ),
+                });
+            }
+
+            let next = self.fat[cluster as usize];
+            if !(2..0x0FFFFFF8).contains(&next) || next as usize >= self.fat.len() {
+                break;
+            }
+            cluster = next;
+        }
+
+        Ok(entries)
+    }
+
+    /// Every used slot of a directory, deleted entries and LFN slots
+    /// included, with the cluster and offset it lives at. Stops at the
+    /// end-of-directory marker.
+    pub fn raw_entries(&mut self, start_cluster: u32) -> std::io::Result<Vec<(u32, usize, [u8; 32])>> {
+        let mut slots = Vec::new();
+        let mut cluster = start_cluster;
+        let mut seen = 0u32;
+
+        loop {
+            let buffer = self.read_cluster(cluster)?;
+            for (i, chunk) in buffer.chunks_exact(32).enumerate() {
+                if chunk[0] == 0x00 {
+                    return Ok(slots);
+                }
+                let mut entry = [0u8; 32];
+                entry.copy_from_slice(chunk);
+                slots.push((cluster, i * 32, entry));
+            }
+
+            seen += 1;
+            let next = self.fat[cluster as usize] & 0x0FFFFFFF;
+            if !(2..0x0FFFFFF8).contains(&next) || next > self.max_cluster() || seen > self.max_cluster() {
+                return Ok(slots);
+            }
+            cluster = next;
+        }
+    }
+
+    /// Follows a '/'-separated directory path from `cwd_cluster` (or the root
+    /// when it starts with '/') and returns the cluster of the final directory.
+    pub fn resolve_directory(&mut self, cwd_cluster: u32, path: &str) -> Option<u32> {
+        let root = self.bpb.bpb_root_clus;
+        let mut cluster = if path.starts_with('/') { root } else { cwd_cluster };
+
+        for component in path.split('/').filter(|c| !c.is_empty() && *c != ".") {
+            if component == ".." && cluster == root {
+                continue;
+            }
+            let (entry_cluster, entry_offset) = self.find_entry_in_directory(cluster, component)?;
+            let entry = self.read_raw_entry(entry_cluster, entry_offset).ok()?;
+            if (entry[11] & 0x10) == 0 {
+                return None;
+            }
+            let hi = u16::from_le_bytes([entry[20], entry[21]]) as u32;
+            let lo = u16::from_le_bytes([entry[26], entry[27]]) as u32;
+            let next = (hi << 16) | lo;
+            cluster = if next == 0 { root } else { next };
+        }
+
+        Some(cluster)
+    }
+
+    /// Resolves a path to the entry it names. The root, which has no entry of
+    /// its own, comes back as a directory named "/".
+    pub fn resolve_entry(&mut self, cwd_cluster: u32, path: &str) -> Option<DirEntry> {
+        let root = self.bpb.bpb_root_clus;
+        let trimmed = path.trim_end_matches('/');
+        let (parent, name) = match trimmed.rfind('/') {
+            Some(i) => (&trimmed[..i + 1], &trimmed[i + 1..]),
+            None => ("", trimmed),
+        };
+
+        if name.is_empty() || name == "." || name == ".." {
+            let cluster = self.resolve_directory(cwd_cluster, path)?;
+            let name = if cluster == root { String::from("/") } else { name.to_string() };
+            return Some(DirEntry { name, long_name: None, attr: 0x10, first_cluster: cluster, size: 0, modified: None });
+        }
+
+        let parent_cluster = self.resolve_directory(cwd_cluster, parent)?;
+        let entry = self
+            .list_directory(parent_cluster)
+            .ok()?
+            .into_iter()
+            .find(|e| e.name.eq_ignore_ascii_case(name))?;
+        // A trailing '/' only names directories
+        if path.ends_with('/') && !entry.is_dir() {
+            return None;
+        }
+        Some(entry)
+    }
+
+    /// Number of clusters in the chain starting at `start`, stopping at the
+    /// first invalid link or repeated cluster.
+    pub fn chain_length(&self, start: u32) -> u32 {
+        self.chain_clusters(start).len() as u32
+    }
+
+    /// The clusters of the chain starting at `start`, in order, stopping at
+    /// the first invalid link or repeated cluster.
+    pub fn chain_clusters(&self, start: u32) -> Vec<u32> {
+        let max_cluster = self.max_cluster();
+        let mut clusters = Vec::new();
+        let mut seen = HashSet::new();
+        let mut cur = start;
+        while cur >= 2 && cur <= max_cluster && seen.insert(cur) {
+            clusters.push(cur);
+            let next = self.fat[cur as usize] & 0x0FFFFFFF;
+            if next >= 0x0FFFFFF8 {
+                break;
+            }
+            cur = next;
+        }
+        clusters
+    }
+
+    /// Overwrites clusters `passes` times: random bytes on every pass but the
+    /// last, which writes zeros. Each pass is synced to the image before the
+    /// next starts, so the passes are not merged in the page cache.
+    pub fn shred_clusters(&mut self, clusters: &[u32], passes: u32) -> std::io::Result<()> {
+        let bytes_per_cluster = self.bpb.bpb_byts_per_sec as usize * self.bpb.bpb_sec_per_clus as usize;
+        let seed = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_nanos() as u64;
+        let mut state = seed | 1;
+
+        for pass in 1..=passes {
+            let mut buf = vec![0u8; bytes_per_cluster];
+            for &cluster in clusters {
+                if pass < passes {
+                    for chunk in buf.chunks_mut(8) {
+                        // xorshift64
+                        state ^= state << 13;
+                        state ^= state >> 7;
+                        state ^= state << 17;
+                        chunk.copy_from_slice(&state.to_le_bytes()[..chunk.len()]);
+                    }
+                }
+                self.write_data_cluster(cluster, &buf)?;
+            }
+            self.file.sync_data()?;
+        }
+        Ok(())
+    }
+
+    /// The slots of the directory entry at `cluster`/`offset`: the long-name
+    /// slots that belong to it, in order, followed by the entry itself.
+    fn entry_slots(&mut self, dir_cluster: u32, cluster: u32, offset: usize) -> std::io::Result<Vec<(u32, usize)>> {
+        let slots = self.raw_entries(dir_cluster)?;
+        let Some(pos) = slots.iter().position(|(c, o, _)| *c == cluster && *o == offset) else {
+            return Err(std::io::Error::new(std::io::ErrorKind::NotFound, "entry not found in directory"));
+        };
+        let checksum = Self::lfn_checksum(&slots[pos].2[0..11]);
+        let mut start = pos;
+        while start > 0 {
+            let slot = &slots[start - 1].2;
+            if slot[11] != 0x0F || slot[0] == 0xE5 || slot[13] != checksum {
+                break;
+            }
+            start -= 1;
+        }
+        Ok(slots[start..=pos].iter().map(|(c, o, _)| (*c, *o)).collect())
+    }
+
+    /// Scrubs a directory entry and the long-name slots that belong to it,
+    /// leaving each slot marked deleted with every other byte zeroed.
+    pub fn wipe_entry(&mut self, dir_cluster: u32, cluster: u32, offset: usize) -> std::io::Result<()> {
+        let mut wiped = [0u8; 32];
+        wiped[0] = 0xE5;
+        for (c, o) in self.entry_slots(dir_cluster, cluster, offset)? {
+            self.write_raw_entry(c, o, &wiped)?;
+        }
+        Ok(())
+    }
+
+    /// Marks a directory entry and the long-name slots that belong to it
+    /// deleted, so no orphaned long name is left behind.
+    pub fn delete_entry(&mut self, dir_cluster: u32, cluster: u32, offset: usize) -> std::io::Result<()> {
+        for (c, o) in self.entry_slots(dir_cluster, cluster, offset)? {
+            self.mark_entry_deleted(c, o)?;
+        }
+        Ok(())
+    }
+
+    /// Marks only the long-name slots of a directory entry deleted, for an
+    /// entry about to be renamed in place.
+    pub fn delete_long_name(&mut self, dir_cluster: u32, cluster: u32, offset: usize) -> std::io::Result<()> {
+        let mut slots = self.entry_slots(dir_cluster, cluster, offset)?;
+        slots.pop();
+        for (c, o) in slots {
+            self.mark_entry_deleted(c, o)?;
+        }
+        Ok(())
+    }
+
+    /// The clusters of a directory and of every directory above it, ending
+    /// with the root.
+    pub fn ancestors(&mut self, cluster: u32) -> Vec<u32> {
+        let root = self.bpb.bpb_root_clus;
+        let mut chain = vec![cluster];
+        let mut cur = cluster;
+        while cur != root {
+            match self.resolve_directory(cur, "..") {
+                Some(parent) if !chain.contains(&parent) => {
+                    chain.push(parent);
+                    cur = parent;
+                }
+                _ => break,
+            }
+        }
+        chain
+    }
+
+    /// Copies the chain starting at `start` into newly allocated clusters and
+    /// returns the first of them, or 0 for an empty chain. If the volume fills
+    /// up partway or the chain loops back on itself, the clusters allocated so
+    /// far are freed again.
+    pub fn copy_chain(&mut self, start: u32) -> std::io::Result<u32> {
+        let max_cluster = self.max_cluster();
+        let mut seen = HashSet::new();
+        let mut first = 0;
+        let mut prev = 0;
+        let mut cur = start;
+
+        while cur >= 2 && cur <= max_cluster {
+            if !seen.insert(cur) {
+                self.dealloc_chain(first)?;
+                return Err(std::io::Error::new(std::io::ErrorKind::InvalidData, "cluster chain loops back on itself; run fsck"));
+            }
+            let Some(copy) = self.alloc_cluster() else {
+                self.dealloc_chain(first)?;
+                return Err(std::io::Error::new(std::io::ErrorKind::StorageFull, "no free clusters left on the volume"));
+            };
+            if prev == 0 {
+                first = copy;
+            } else {
+                self.fat[prev as usize] = copy;
+            }
+            let data = self.read_cluster(cur)?;
+            self.write_data_cluster(copy, &data)?;
+            prev = copy;
+
+            let next = self.fat[cur as usize] & 0x0FFFFFFF;
+            if next >= 0x0FFFFFF8 {
+                break;
+            }
+            cur = next;
+        }
+        Ok(first)
+    }
+
     pub fn initialize_directory_cluster(&mut self, cluster: u32, parent: u32) {
         let bytes_per_sector = self.bpb.bpb_byts_per_sec as usize;
         let sectors = self.bpb.bpb_sec_per_clus as usize;
@@ -141,8 +468,9 @@ impl Volume {
             self.write_directory_entry(entry, ".", 0x10, cluster, 0);
         }
 
-        // ".." entry
+        // ".." entry, which holds 0 rather than the root's cluster number when the parent is the root
         {
+            let parent = if parent == self.bpb.bpb_root_clus { 0 } else { parent };
             let entry = &mut buffer[32..64];
             self.write_directory_entry(entry, "..", 0x10, parent, 0);
         }
@@ -180,43 +508,22 @@ impl Volume {
     }
 
     pub fn write_directory_entry(&mut self, entry: &mut [u8], name: &str, attr: u8, first_cluster: u32, file_size: u32,) {
-        // Format short name: pad or truncate
-        let mut name11 = [b' '; 11];
-
-        if name == "." {
-            name11[0] = b'.';
-        } else if name == ".." {
-            name11[0] = b'.';
-            name11[1] = b'.';
-        } else {
-            let (name_part, ext_part) = if let Some((name, ext)) = name.split_once('.') {
-                (name, ext)
-            } else {
-                (name, "")
-            };
-
-            // Copy name (max 8 chars)
-            for (i, b) in name_part.bytes().take(8).enumerate() {
-                name11[i] = b.to_ascii_uppercase();
-            }
-
-            // Copy extension (max 3 chars)
-            for (i, b) in ext_part.bytes().take(3).enumerate() {
-                name11[8 + i] = b.to_ascii_uppercase();
-            }
-        }
-
+        let name11 = short_name(name);
         entry[..11].copy_from_slice(&name11);
         entry[11] = attr; // attribute
 
-        // Zero time/date fields
+        // Created, accessed and modified now
+        let (date, time) = fat_now();
         entry[12] = 0;
         entry[13] = 0;
-        entry[14..20].fill(0);
+        entry[14..16].copy_from_slice(&time.to_le_bytes());
+        entry[16..18].copy_from_slice(&date.to_le_bytes());
+        entry[18..20].copy_from_slice(&date.to_le_bytes());
 
         // First cluster
         entry[20..22].copy_from_slice(&((first_cluster >> 16) as u16).to_le_bytes());
-        entry[22..26].fill(0);
+        entry[22..24].copy_from_slice(&time.to_le_bytes());
+        entry[24..26].copy_from_slice(&date.to_le_bytes());
         entry[26..28].copy_from_slice(&(first_cluster as u16).to_le_bytes());
         // File size
         entry[28..32].copy_from_slice(&file_size.to_le_bytes());
@@ -233,19 +540,73 @@ impl Volume {
             fat_raw[i * 4..i * 4 + 4].copy_from_slice(&bytes);
         }
 
-        let fat_start_sector = self.first_fat_sector;
+        // Every FAT copy gets the same table so the mirrors never drift apart
+        for copy in 0..self.bpb.bpb_num_fats as u32 {
+            let fat_start_sector = self.first_fat_sector + copy * self.bpb.bpb_fatsz32;
 
-        for sector in 0..self.bpb.bpb_fatsz32 {
-            let offset = (sector as usize) * bytes_per_sector;
-            self.write_sector(
-                fat_start_sector + sector,
-                &fat_raw[offset..offset + bytes_per_sector],
-            )?;
+            for sector in 0..self.bpb.bpb_fatsz32 {
+                let offset = (sector as usize) * bytes_per_sector;
+                self.write_sector(
+                    fat_start_sector + sector,
+                    &fat_raw[offset..offset + bytes_per_sector],
+                )?;
+            }
         }
 
+        // Keep the cached free count honest so other systems see the right free space
+        self.update_fsinfo()?;
         Ok(())
     }
 
+    /// Number of data clusters the FAT marks as free.
+    pub fn free_clusters(&self) -> u32 {
+        (2..=self.max_cluster()).filter(|&c| self.fat[c as usize] & 0x0FFFFFFF == 0).count() as u32
+    }
+
+    /// Free-cluster count and next-free hint stored in FSInfo, or None when
+    /// the volume has no valid FSInfo sector.
+    pub fn read_fsinfo(&mut self) -> std::io::Result<Option<(u32, u32)>> {
+        let sector = self.bpb.bpb_fs_info as u32;
+        if sector == 0 || sector >= self.bpb.bpb_rsvd_sec_cnt as u32 {
+            return Ok(None);
+        }
+        let mut buf = vec![0u8; self.bpb.bpb_byts_per_sec as usize];
+        self.read_sector(sector, &mut buf)?;
+
+        let lead_sig = u32::from_le_bytes([buf[0], buf[1], buf[2], buf[3]]);
+        let struct_sig = u32::from_le_bytes([buf[484], buf[485], buf[486], buf[487]]);
+        if lead_sig != 0x41615252 || struct_sig != 0x61417272 {
+            return Ok(None);
+        }
+        let free = u32::from_le_bytes([buf[488], buf[489], buf[490], buf[491]]);
+        let next_free = u32::from_le_bytes([buf[492], buf[493], buf[494], buf[495]]);
+        Ok(Some((free, next_free)))
+    }
+
+    /// Recomputes the FSInfo free-cluster count and next-free hint from the FAT
+    /// and writes them back. Returns the number of free clusters.
+    pub fn update_fsinfo(&mut self) -> std::io::Result<u32> {
+        let max_cluster = self.max_cluster();
+        let free = self.free_clusters();
+        let next_free = (2..=max_cluster).find(|&c| self.fat[c as usize] == 0).unwrap_or(0xFFFFFFFF);
+
+        let sector = self.bpb.bpb_fs_info as u32;
+        if sector == 0 || sector >= self.bpb.bpb_rsvd_sec_cnt as u32 {
+            return Ok(free); // the volume has no FSInfo sector
+        }
+        let mut buf = vec![0u8; self.bpb.bpb_byts_per_sec as usize];
+        self.read_sector(sector, &mut buf)?;
+
+        // Rewrite the signatures too in case they were what got damaged
+        buf[0..4].copy_from_slice(&0x41615252u32.to_le_bytes());
+        buf[484..488].copy_from_slice(&0x61417272u32.to_le_bytes());
+        buf[488..492].copy_from_slice(&free.to_le_bytes());
+        buf[492..496].copy_from_slice(&next_free.to_le_bytes());
+        buf[508..512].copy_from_slice(&0xAA550000u32.to_le_bytes());
+        self.write_sector(sector, &buf)?;
+        Ok(free)
+    }
+
     pub fn parse_short_name(&self, raw_name: &[u8]) -> String {
         let name = String::from_utf8_lossy(&raw_name[0..8]).trim().to_string();
         let ext = String::from_utf8_lossy(&raw_name[8..11]).trim().to_string();
@@ -258,6 +619,15 @@ impl Volume {
 
     pub fn read_sector(&mut self, sector: u32, buf: &mut [u8]) -> std::io::Result<()> {
         let offset = sector as u64 * self.bpb.bpb_byts_per_sec as u64;
+        if let Some(staged) = self.journal.as_ref().and_then(|j| j.staged(offset)) {
+            buf.copy_from_slice(staged);
+            return Ok(());
+        }
+        if let Some(overlay) = self.overlay.as_mut()
+            && overlay.read(sector, buf)?
+        {
+            return Ok(());
+        }
         self.file.seek(SeekFrom::Start(offset))?;
         self.file.read_exact(buf)?;
         Ok(())
@@ -278,10 +648,123 @@ impl Volume {
         }
 
         let offset = sector as u64 * bytes_per_sector as u64;
+        if self.overlay.is_some() || self.journal.is_some() {
+            // flush_fat rewrites the whole table; only sectors that change
+            // need to go to the overlay or through the journal
+            let mut current = vec![0u8; bytes_per_sector];
+            self.read_sector(sector, &mut current)?;
+            if current == buf {
+                return Ok(());
+            }
+        }
+        if let Some(overlay) = self.overlay.as_mut() {
+            return overlay.write(sector, buf);
+        }
+        if let Some(journal) = self.journal.as_mut() {
+            journal.stage(offset, buf);
+            return Ok(());
+        }
+        self.file.seek(SeekFrom::Start(offset))?;
+        self.file.write_all(buf)?;
+        self.file.flush()?;
+
+        Ok(())
+    }
+
+    /// Writes a sector of file data straight to the image, even when the
+    /// journal is on. As in ordered journaling, data lands before the
+    /// metadata that points at it is committed, so the journal stays small
+    /// and a crash never leaves an entry referring to unwritten data.
+    pub fn write_data_sector(&mut self, sector: u32, buf: &[u8]) -> std::io::Result<()> {
+        if let Some(overlay) = self.overlay.as_mut() {
+            return overlay.write(sector, buf);
+        }
+        let offset = sector as u64 * self.bpb.bpb_byts_per_sec as u64;
+        // A cluster freed and reused within the same command must not be
+        // overwritten again by its old staged contents at commit
+        if let Some(journal) = self.journal.as_mut() {
+            journal.unstage(offset);
+        }
         self.file.seek(SeekFrom::Start(offset))?;
         self.file.write_all(buf)?;
         self.file.flush()?;
+        Ok(())
+    }
 
+    /// Commits the metadata the current command staged in the journal, if
+    /// the journal is on.
+    pub fn commit_journal(&mut self) -> std::io::Result<()> {
+        if let Some(journal) = self.journal.as_mut() {
+            journal.commit(&mut self.file)?;
+        }
+        Ok(())
+    }
+
+    /// Copies every sector held in the overlay into the image and empties
+    /// the overlay once the image is synced. Returns how many sectors moved.
+    ///
+    /// With the journal on, the sectors go through it and land all at once.
+    /// Without it the FAT is written last, so a crash partway leaves at worst
+    /// entries whose chains the FAT still calls free, which fsck reports.
+    pub fn commit_overlay(&mut self) -> std::io::Result<usize> {
+        let Some(overlay) = self.overlay.as_mut() else {
+            return Ok(0);
+        };
+        let bytes_per_sector = self.bpb.bpb_byts_per_sec as usize;
+        let fat_sectors = self.first_fat_sector..self.first_data_sector;
+        let mut sectors = overlay.sectors();
+        sectors.sort_by_key(|s| fat_sectors.contains(s));
+
+        let mut buf = vec![0u8; bytes_per_sector];
+        for &sector in &sectors {
+            overlay.read(sector, &mut buf)?;
+            let offset = sector as u64 * bytes_per_sector as u64;
+            if let Some(journal) = self.journal.as_mut() {
+                journal.stage(offset, &buf);
+            } else {
+                self.file.seek(SeekFrom::Start(offset))?;
+                self.file.write_all(&buf)?;
+            }
+        }
+        match self.journal.as_mut() {
+            Some(journal) => {
+                journal.commit(&mut self.file)?;
+            }
+            None => self.file.sync_data()?,
+        }
+        overlay.clear(self.bpb.bs_vol_id)?;
+        Ok(sectors.len())
+    }
+
+    /// Throws away everything in the overlay and reloads the boot sector and
+    /// FAT from the untouched image. Returns how many sectors were dropped.
+    pub fn discard_overlay(&mut self) -> std::io::Result<usize> {
+        let Some(overlay) = self.overlay.as_mut() else {
+            return Ok(0);
+        };
+        let dropped = overlay.sectors().len();
+        let serial = overlay.serial();
+        overlay.clear(serial)?;
+        self.reload()?;
+        Ok(dropped)
+    }
+
+    /// Re-reads the boot sector and FAT through `read_sector`, for when the
+    /// sectors under the cached copies change, as when an overlay is attached
+    /// or discarded.
+    pub fn reload(&mut self) -> std::io::Result<()> {
+        let bytes_per_sector = self.bpb.bpb_byts_per_sec as usize;
+        let mut boot = vec![0u8; bytes_per_sector];
+        self.read_sector(0, &mut boot)?;
+        self.bpb = BootSector::parse(&boot, self.bpb.file_size);
+
+        let mut fat = Vec::with_capacity(self.fat.len());
+        let mut buf = vec![0u8; bytes_per_sector];
+        for sector in 0..self.bpb.bpb_fatsz32 {
+            self.read_sector(self.first_fat_sector + sector, &mut buf)?;
+            fat.extend(buf.chunks_exact(4).map(|chunk| u32::from_le_bytes(chunk.try_into().unwrap())));
+        }
+        self.fat = fat;
         Ok(())
     }
 
@@ -327,6 +810,11 @@ impl Volume {
                     entry[26..28].copy_from_slice(&lo.to_le_bytes());
                     entry[28..32].copy_from_slice(&new_size.to_le_bytes());
 
+                    let (date, time) = fat_now();
+                    entry[18..20].copy_from_slice(&date.to_le_bytes());
+                    entry[22..24].copy_from_slice(&time.to_le_bytes());
+                    entry[24..26].copy_from_slice(&date.to_le_bytes());
+
                     for s in 0..sectors_per_cluster {
                         let sector_num = first_sector + s as u32;
                         let start = s * bytes_per_sector;
@@ -349,6 +837,138 @@ impl Volume {
         }
     }
 
+    pub fn read_cluster(&mut self, cluster: u32) -> std::io::Result<Vec<u8>> {
+        let bytes_per_sector = self.bpb.bpb_byts_per_sec as usize;
+        let sectors = self.bpb.bpb_sec_per_clus as usize;
+
+        let first_sector = self.get_first_sector_of_cluster(cluster);
+        let mut buf = vec![0u8; bytes_per_sector * sectors];
+        for s in 0..sectors {
+            self.read_sector(
+                first_sector + s as u32,
+                &mut buf[s * bytes_per_sector..(s + 1) * bytes_per_sector],
+            )?;
+        }
+        Ok(buf)
+    }
+
+    /// The 13 UTF-16 code units a long-name slot holds.
+    fn lfn_chars(slot: &[u8]) -> Vec<u16> {
+        [1, 3, 5, 7, 9, 14, 16, 18, 20, 22, 24, 28, 30]
+            .iter()
+            .map(|&i| u16::from_le_bytes([slot[i], slot[i + 1]]))
+            .collect()
+    }
+
+    pub fn write_cluster(&mut self, cluster: u32, buf: &[u8]) -> std::io::Result<()> {
+        let bytes_per_sector = self.bpb.bpb_byts_per_sec as usize;
+        let sectors = self.bpb.bpb_sec_per_clus as usize;
+
+        let first_sector = self.get_first_sector_of_cluster(cluster);
+        for s in 0..sectors {
+            self.write_sector(first_sector + s as u32, &buf[s * bytes_per_sector..(s + 1) * bytes_per_sector])?;
+        }
+        Ok(())
+    }
+
+    /// Like `write_cluster`, for file data that bypasses the journal.
+    pub fn write_data_cluster(&mut self, cluster: u32, buf: &[u8]) -> std::io::Result<()> {
+        let bytes_per_sector = self.bpb.bpb_byts_per_sec as usize;
+        let sectors = self.bpb.bpb_sec_per_clus as usize;
+
+        let first_sector = self.get_first_sector_of_cluster(cluster);
+        for s in 0..sectors {
+            self.write_data_sector(first_sector + s as u32, &buf[s * bytes_per_sector..(s + 1) * bytes_per_sector])?;
+        }
+        Ok(())
+    }
+
+    /// Writes a new entry into the first free slot of a directory, growing the
+    /// directory by a zeroed cluster when it is full.
+    pub fn add_directory_entry(&mut self, dir_cluster: u32, name: &str, attr: u8, first_cluster: u32, size: u32) -> std::io::Result<(u32, usize)> {
+        let mut entry = [0u8; 32];
+        self.write_directory_entry(&mut entry, name, attr, first_cluster, size);
+        self.add_raw_entry(dir_cluster, &entry)
+    }
+
+    pub fn add_raw_entry(&mut self, dir_cluster: u32, entry: &[u8; 32]) -> std::io::Result<(u32, usize)> {
+        let (cluster, offset) = self.free_entry_slot(dir_cluster)?;
+        self.write_raw_entry(cluster, offset, entry)?;
+        Ok((cluster, offset))
+    }
+
+    /// Finds an unused entry in a directory, growing the directory by a
+    /// zeroed cluster when it is full. The growth is only in the in-memory FAT
+    /// until the next flush.
+    pub fn free_entry_slot(&mut self, dir_cluster: u32) -> std::io::Result<(u32, usize)> {
+        if let Some(slot) = self.find_free_directory_entry(dir_cluster) {
+            return Ok(slot);
+        }
+        let new_cluster = self.append_cluster(dir_cluster).ok_or_else(|| {
+            std::io::Error::new(std::io::ErrorKind::StorageFull, "no free clusters to grow the directory")
+        })?;
+        let bytes_per_cluster = self.bpb.bpb_byts_per_sec as usize * self.bpb.bpb_sec_per_clus as usize;
+        self.write_cluster(new_cluster, &vec![0u8; bytes_per_cluster])?;
+        Ok((new_cluster, 0))
+    }
+
+    /// Patches bytes of the boot sector, and of its backup copy when the
+    /// volume has one, starting at `offset`.
+    pub fn write_boot_sector_field(&mut self, offset: usize, bytes: &[u8]) -> std::io::Result<()> {
+        let backup = self.bpb.bpb_bk_boot_sec as u32;
+        let mut sectors = vec![0];
+        if backup != 0 && backup < self.bpb.bpb_rsvd_sec_cnt as u32 {
+            sectors.push(backup);
+        }
+
+        let mut buf = vec![0u8; self.bpb.bpb_byts_per_sec as usize];
+        for sector in sectors {
+            self.read_sector(sector, &mut buf)?;
+            buf[offset..offset + bytes.len()].copy_from_slice(bytes);
+            self.write_sector(sector, &buf)?;
+        }
+        Ok(())
+    }
+
+    /// Finds the volume label entry in the root directory.
+    pub fn find_label_entry(&mut self) -> std::io::Result<Option<(u32, usize)>> {
+        let bytes_per_cluster = self.bpb.bpb_byts_per_sec as usize * self.bpb.bpb_sec_per_clus as usize;
+        let mut cluster = self.bpb.bpb_root_clus;
+
+        loop {
+            let buf = self.read_cluster(cluster)?;
+            for offset in (0..bytes_per_cluster).step_by(32) {
+                let entry = &buf[offset..offset + 32];
+                if entry[0] == 0x00 {
+                    return Ok(None);
+                }
+                if entry[0] != 0xE5 && entry[11] != 0x0F && (entry[11] & 0x08) != 0 {
+                    return Ok(Some((cluster, offset)));
+                }
+            }
+
+            let next = self.fat[cluster as usize];
+            if next >= 0x0FFFFFF8 {
+                return Ok(None);
+            }
+            cluster = next;
+        }
+    }
+
+    /// Checksum of an 8.3 name stored in each of its long-name slots.
+    pub fn lfn_checksum(short_name: &[u8]) -> u8 {
+        short_name[..11]
+            .iter()
+            .fold(0u8, |sum, &b| sum.rotate_right(1).wrapping_add(b))
+    }
+
+    /// Number of the last valid data cluster on the volume.
+    pub fn max_cluster(&self) -> u32 {
+        let data_sectors = self.bpb.bpb_tot_sec32.saturating_sub(self.first_data_sector);
+        let cluster_count = data_sectors / self.bpb.bpb_sec_per_clus as u32;
+        (cluster_count + 1).min(self.fat.len() as u32 - 1)
+    }
+
     pub fn read_raw_entry(&mut self, cluster: u32, offset: usize) -> std::io::Result<[u8; 32]> {
         let bytes_per_sector = self.bpb.bpb_byts_per_sec as usize;
         let sectors = self.bpb.bpb_sec_per_clus as usize;
@@ -399,25 +1019,45 @@ impl Volume {
         Ok(())
     }
 
+    /// Frees the chain starting at `start`. Only the clusters `chain_clusters`
+    /// finds are freed, so a chain that loops or points out of range never
+    /// touches the reserved entries FAT[0] and FAT[1].
     pub fn dealloc_chain(&mut self, start: u32) -> std::io::Result<()> {
-        if start == 0 { return Ok(()); }
+        for cluster in self.chain_clusters(start) {
+            self.fat[cluster as usize] = 0;
+        }
+        Ok(())
+    }
 
-        let mut cur = start as usize;
-        loop {
-            if cur >= self.fat.len() { break; }
+    pub fn read_file(&mut self, start_cluster: u32, size: u32) -> std::io::Result<Vec<u8>> {
+        let bytes_per_sector = self.bpb.bpb_byts_per_sec as usize;
+        let sectors = self.bpb.bpb_sec_per_clus as usize;
+        let bytes_per_cluster = bytes_per_sector * sectors;
+
+        let mut data = Vec::with_capacity(size as usize);
+        let mut cluster = start_cluster;
+        let mut buf = vec![0u8; bytes_per_cluster];
 
-            let next = self.fat[cur];
+        while data.len() < size as usize && cluster >= 2 && (cluster as usize) < self.fat.len() {
+            let first_sector = self.get_first_sector_of_cluster(cluster);
+            for s in 0..sectors {
+                self.read_sector(
+                    first_sector + s as u32,
+                    &mut buf[s * bytes_per_sector..(s + 1) * bytes_per_sector],
+                )?;
+            }
 
-            self.fat[cur] = 0;
+            let take = (size as usize - data.len()).min(bytes_per_cluster);
+            data.extend_from_slice(&buf[..take]);
 
+            let next = self.fat[cluster as usize];
             if next >= 0x0FFFFFF8 {
                 break;
             }
-
-            cur = next as usize;
+            cluster = next;
         }
 
-        Ok(())
+        Ok(data)
     }
 
     pub fn mark_entry_deleted(&mut self, cluster: u32, offset: usize) -> std::io::Result<()> {
@@ -427,10 +1067,104 @@ impl Volume {
     }
 
     pub fn set_entry_name(&self, entry: &mut [u8], new_name: &str) {
-        let mut name11 = [b' '; 11];
-        for (i, b) in new_name.bytes().take(11).enumerate() {
-            name11[i] = b.to_ascii_uppercase();
-        }
-        entry[..11].copy_from_slice(&name11);
+        entry[..11].copy_from_slice(&short_name(new_name));
+    }
+}
+
+/// Refuses a change to the raw directory entry of `name` that its attributes
+/// forbid: removing, renaming, replacing or writing a read-only or system
+/// entry without `force`, or touching the volume label or an LFN slot at all.
+pub fn check_modify(raw: &[u8; 32], name: &str, force: bool) -> Result<(), PermissionError> {
+    let attr = raw[11];
+    if attr & 0x0F == 0x0F {
+        Err(PermissionError::LongName(name.to_string()))
+    } else if attr & 0x08 != 0 {
+        Err(PermissionError::VolumeLabel(name.to_string()))
+    } else if force {
+        Ok(())
+    } else if attr & 0x01 != 0 {
+        Err(PermissionError::ReadOnly(name.to_string()))
+    } else if attr & 0x04 != 0 {
+        Err(PermissionError::System(name.to_string()))
+    } else {
+        Ok(())
+    }
+}
+
+/// Lays a name out as the 11 space-padded bytes of an 8.3 directory entry,
+/// upper-cased and truncated to 8 and 3 characters.
+pub fn short_name(name: &str) -> [u8; 11] {
+    let mut name11 = [b' '; 11];
+
+    if name == "." || name == ".." {
+        name11[..name.len()].copy_from_slice(name.as_bytes());
+        return name11;
     }
-}
\ No newline at end of file
+
+    let (name_part, ext_part) = name.split_once('.').unwrap_or((name, ""));
+    for (i, b) in name_part.bytes().take(8).enumerate() {
+        name11[i] = b.to_ascii_uppercase();
+    }
+    for (i, b) in ext_part.bytes().take(3).enumerate() {
+        name11[8 + i] = b.to_ascii_uppercase();
+    }
+    name11
+}
+
+// Days between 1970-01-01 and a proleptic Gregorian date, and back
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let y = if month <= 2 { year - 1 } else { year };
+    let era = y.div_euclid(400);
+    let yoe = y - era * 400;
+    let doy = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146097 + doe - 719468
+}
+
+fn civil_from_days(days: i64) -> (i64, i64, i64) {
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z - era * 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    (yoe + era * 400 + if month <= 2 { 1 } else { 0 }, month, day)
+}
+
+/// The current time as a FAT (date, time) pair. FAT stores local time, but
+/// without a time zone database the shell stamps entries in UTC.
+pub fn fat_now() -> (u16, u16) {
+    let secs = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs() as i64).unwrap_or(0);
+    unix_to_fat(secs)
+}
+
+/// Converts seconds since the Unix epoch to a FAT (date, time) pair, clamped
+/// to the 1980-2107 range FAT can store.
+pub fn unix_to_fat(secs: i64) -> (u16, u16) {
+    let (year, month, day) = civil_from_days(secs.div_euclid(86400));
+    if year < 1980 {
+        return ((1 << 5) | 1, 0);
+    }
+    if year > 2107 {
+        return ((127 << 9) | (12 << 5) | 31, (23 << 11) | (59 << 5) | 29);
+    }
+    let of_day = secs.rem_euclid(86400);
+    let date = (((year - 1980) << 9) | (month << 5) | day) as u16;
+    let time = (((of_day / 3600) << 11) | ((of_day / 60 % 60) << 5) | (of_day % 60 / 2)) as u16;
+    (date, time)
+}
+
+/// Seconds since the Unix epoch for a FAT date and time, or None when the
+/// date was never set.
+pub fn fat_to_unix(date: u16, time: u16) -> Option<i64> {
+    let month = ((date >> 5) & 0x0F) as i64;
+    let day = (date & 0x1F) as i64;
+    if !(1..=12).contains(&month) || day == 0 {
+        return None;
+    }
+    let year = 1980 + (date >> 9) as i64;
+    let secs = ((time >> 11) as i64) * 3600 + (((time >> 5) & 0x3F) as i64) * 60 + ((time & 0x1F) as i64) * 2;
+    Some(days_from_civil(year, month, day) * 86400 + secs)
+}
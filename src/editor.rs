@@ -0,0 +1,172 @@
+use std::cell::RefCell;
+use std::env;
+use std::path::PathBuf;
+
+use rustyline::completion::{Completer, Pair};
+use rustyline::error::ReadlineError;
+use rustyline::highlight::Highlighter;
+use rustyline::hint::Hinter;
+use rustyline::history::DefaultHistory;
+use rustyline::validate::Validator;
+use rustyline::{CompletionType, Config, Context, Editor, Helper};
+
+use crate::commands::BUILTINS;
+use crate::executor::execute_command;
+use crate::models::ShellCore;
+
+const HISTORY_FILE: &str = ".rust_fat32_history";
+
+/// Owns the shell while the interactive editor runs so tab completion can
+/// read directories from the image between commands.
+struct ShellHelper {
+    shell: RefCell<ShellCore>,
+}
+
+impl Completer for ShellHelper {
+    type Candidate = Pair;
+
+    fn complete(&self, line: &str, pos: usize, _ctx: &Context<'_>) -> rustyline::Result<(usize, Vec<Pair>)> {
+        let before = &line[..pos];
+        let start = before
+            .rfind(|c: char| c.is_whitespace() || c == '|' || c == ';' || c == '&')
+            .map(|i| i + 1)
+            .unwrap_or(0);
+        let word = &before[start..];
+
+        // The first word of a command is a builtin name, anything after it a path
+        let preceding = before[..start].trim_end();
+        let is_command = preceding.is_empty()
+            || preceding.ends_with('|')
+            || preceding.ends_with(';')
+            || preceding.ends_with('&');
+
+        if is_command {
+            let candidates = BUILTINS
+                .iter()
+                .filter(|name| name.starts_with(word))
+                .map(|name| Pair {
+                    display: name.to_string(),
+                    replacement: format!("{} ", name),
+                })
+                .collect();
+            return Ok((start, candidates));
+        }
+
+        Ok((start, self.complete_path(word)))
+    }
+}
+
+impl ShellHelper {
+    fn complete_path(&self, word: &str) -> Vec<Pair> {
+        let (dir_part, prefix) = match word.rfind('/') {
+            Some(i) => (&word[..i + 1], &word[i + 1..]),
+            None => ("", word),
+        };
+
+        let mut shell = self.shell.borrow_mut();
+        let cwd = shell.cwd_cluster;
+        let dir_cluster = match shell.vol.resolve_directory(cwd, dir_part) {
+            Some(c) => c,
+            None => return Vec::new(),
+        };
+        let entries = match shell.vol.list_directory(dir_cluster) {
+            Ok(e) => e,
+            Err(_) => return Vec::new(),
+        };
+
+        let prefix_upper = prefix.to_ascii_uppercase();
+        let mut candidates: Vec<Pair> = entries
+            .iter()
+            .filter(|e| e.name != "." && e.name != "..")
+            .filter(|e| e.name.to_ascii_uppercase().starts_with(&prefix_upper))
+            .map(|e| {
+                let suffix = if e.is_dir() { "/" } else { " " };
+                Pair {
+                    display: format!("{}{}", e.name, if e.is_dir() { "/" } else { "" }),
+                    replacement: format!("{}{}{}", dir_part, e.name, suffix),
+                }
+            })
+            .collect();
+        candidates.sort_by(|a, b| a.display.cmp(&b.display));
+        candidates
+    }
+}
+
+impl Hinter for ShellHelper {
+    type Hint = String;
+}
+
+impl Highlighter for ShellHelper {}
+
+impl Validator for ShellHelper {}
+
+impl Helper for ShellHelper {}
+
+fn history_path() -> Option<PathBuf> {
+    env::var_os("HOME").map(|home| PathBuf::from(home).join(HISTORY_FILE))
+}
+
+/// Runs the interactive prompt with line editing, Ctrl-R history search,
+/// history persisted in `~/.rust_fat32_history` and tab completion.
+pub fn run_interactive(image_name: &str, shell: ShellCore) -> i32 {
+    let config = Config::builder()
+        .completion_type(CompletionType::List)
+        .build();
+    let mut editor: Editor<ShellHelper, DefaultHistory> = match Editor::with_config(config) {
+        Ok(e) => e,
+        Err(e) => {
+            eprintln!("Failed to initialise line editor: {}", e);
+            return 1;
+        }
+    };
+    editor.set_helper(Some(ShellHelper { shell: RefCell::new(shell) }));
+
+    let history = history_path();
+    if let Some(path) = &history {
+        // A missing history file just means this is the first session
+        editor.load_history(path).ok();
+    }
+
+    loop {
+        let prompt = {
+            let shell = editor.helper().unwrap().shell.borrow();
+            let mode = if shell.vol.overlay.is_some() {
+                "[overlay]"
+            } else if shell.read_only {
+                "[ro]"
+            } else {
+                ""
+            };
+            format!("{}{}{}>", image_name, mode, shell.cwd_path)
+        };
+
+        let line = match editor.readline(&prompt) {
+            Ok(line) => line,
+            Err(ReadlineError::Interrupted) => continue,
+            Err(ReadlineError::Eof) => break,
+            Err(e) => {
+                eprintln!("Failed to read input: {}", e);
+                return 1;
+            }
+        };
+        let command = line.trim();
+        if command.is_empty() {
+            continue;
+        }
+
+        editor.add_history_entry(command).ok();
+        // Saved before running the command because `exit` ends the process
+        if let Some(path) = &history {
+            editor.append_history(path).ok();
+        }
+
+        if command.starts_with('#') {
+            continue;
+        }
+        let shell = editor.helper_mut().unwrap().shell.get_mut();
+        execute_command(command, shell);
+    }
+
+    println!();
+    editor.helper().unwrap().shell.borrow().last_status
+}
@@ -1,7 +1,9 @@
+use std::io::{Write, stdout};
+
 use crate::commands::*;
 use crate::{
     models::ShellCore, 
-    parser::{expand_tokens, tokenize}};
+    parser::{expand_aliases, expand_tokens, split_list, tokenize, ListOp}};
 
 
 struct CommandPart {
@@ -26,26 +28,54 @@ impl PartialEq for Direction {
     }
 }
 
-pub fn execute_command(command: &str, shell: &mut ShellCore) {
-    // Phase 1: Tokenization and Expansion
-    let tokens: Vec<_> = tokenize(command);
-    let expanded_tokens: Vec<String> = expand_tokens(tokens);
+/// Runs one line of input and returns the status of the last pipeline that ran.
+pub fn execute_command(command: &str, shell: &mut ShellCore) -> i32 {
+    // Phase 1: Tokenization
+    let tokens: Vec<_> = expand_aliases(tokenize(command), shell);
+    let list = split_list(tokens);
+    let next_ops: Vec<ListOp> = list.iter().skip(1).map(|(op, _)| *op).collect();
+
+    for (i, (op, pipeline)) in list.into_iter().enumerate() {
+        let should_run = match op {
+            ListOp::Always => true,
+            ListOp::And => shell.last_status == 0,
+            ListOp::Or => shell.last_status != 0,
+        };
+        if !should_run {
+            continue;
+        }
+
+        // Phase 2: Expansion, done per pipeline so `$?` sees the previous status
+        let expanded_tokens: Vec<(String, bool)> = expand_tokens(pipeline, shell);
+        if expanded_tokens.is_empty() {
+            continue;
+        }
+
+        // Phase 3: Interpretation and Execution
+        let commands: Vec<CommandPart> = interpret_tokens(expanded_tokens);
+        shell.last_status = execute(commands, shell);
 
-    // Phase 2: Interpretation and Execution
-    let commands: Vec<CommandPart> = interpret_tokens(expanded_tokens);
-    execute(commands, shell);
+        // Like sh, a failure that is tested by a following && or || does not trip set -e
+        let tested = next_ops.get(i).is_some_and(|op| *op != ListOp::Always);
+        if shell.errexit && shell.last_status != 0 && !tested {
+            std::process::exit(shell.last_status);
+        }
+    }
+    shell.last_status
 }
 
-fn interpret_tokens(tokens: Vec<String>) -> Vec<CommandPart> {
+fn interpret_tokens(tokens: Vec<(String, bool)>) -> Vec<CommandPart> {
     let mut command_parts: Vec<CommandPart> = Vec::new();
     let mut current_part: Option<CommandPart> = None;
 
     let mut tokens_iter = tokens.iter().peekable();
-    while let Some(t) = tokens_iter.next() {
+    while let Some((t, quoted)) = tokens_iter.next() {
         // The first token is always the program
         // The rest are arguments until I hit a special token
 
-        if current_part.is_none() {
+        if let Some(part) = current_part.as_mut() {
+            part.args.push(t.clone());
+        } else {
             current_part = Some(CommandPart {
                 program: t.clone(),
                 args: Vec::new(),
@@ -55,10 +85,11 @@ fn interpret_tokens(tokens: Vec<String>) -> Vec<CommandPart> {
                 background: false,
                 parse_error: None,
             });
-        } else {
-            current_part.as_mut().unwrap().args.push(t.clone());
         }
 
+        if *quoted {
+            continue;
+        }
         if t == "|" {
             current_part.as_mut().unwrap().args.pop();
             current_part.as_mut().unwrap().direction = Some(Direction::Pipe);
@@ -67,7 +98,7 @@ fn interpret_tokens(tokens: Vec<String>) -> Vec<CommandPart> {
         } else if t == ">" {
             current_part.as_mut().unwrap().args.pop(); // remove ">" from args
             if let Some(next_token) = tokens_iter.next() {
-                let filename = next_token.to_string();
+                let filename = next_token.0.clone();
                 current_part.as_mut().unwrap().redir_out = Some(filename);
             } else {
                 current_part.as_mut().unwrap().parse_error = Some("missing output file after '>'");
@@ -75,7 +106,7 @@ fn interpret_tokens(tokens: Vec<String>) -> Vec<CommandPart> {
         } else if t == "<" {
             current_part.as_mut().unwrap().args.pop(); // remove "<" from args
             if let Some(next_token) = tokens_iter.next() {
-                let filename = next_token.to_string();
+                let filename = next_token.0.clone();
                 current_part.as_mut().unwrap().redir_in = Some(filename);
             } else {
                 current_part.as_mut().unwrap().parse_error = Some("missing input file after '<'");
@@ -85,47 +116,48 @@ fn interpret_tokens(tokens: Vec<String>) -> Vec<CommandPart> {
             current_part.as_mut().unwrap().args.pop();
             command_parts.push(current_part.take().unwrap());
             current_part = None;
-        } else {
-            if t == tokens.last().unwrap() && current_part.is_some() {
-                command_parts.push(current_part.take().unwrap());
-            }
         }
     }
     if let Some(final_part) = current_part.take() {
-            command_parts.push(final_part);
-        }
-    
-    // For debugging purposes
-    // println!("Interpreted Command Parts: ");
-    // for part in command_parts.iter() {
-    //     println!("Program: {}", part.program.to_str().unwrap());
-    //     for arg in part.args.iter() {
-    //         println!("Arg: {}", arg.to_str().unwrap());
-    //     }
-    //     if let Some(dir) = &part.direction {
-    //         match dir {
-    //             Direction::Pipe => println!("Direction: Pipe"),
-    //         }
-    //     } else {
-    //         println!("Direction: None");
-    //     }
-    //     if let Some(redir_out) = &part.redir_out {
-    //         println!("Redir Out: {}", redir_out);
-    //     }
-    //     if let Some(redir_in) = &part.redir_in {
-    //         println!("Redir In: {}", redir_in);
-    //     }
-    //     println!("Background: {}", part.background);
-    // }
+        command_parts.push(final_part);
+    }
     command_parts
 }
 
-fn execute(commands: Vec<CommandPart>, shell: &mut ShellCore) {
+/// Runs a pipeline; its status is the status of the last stage. Every stage
+/// needs the shell mutably, so stages run one after another and each piped
+/// stage's whole output is held in memory until the next one reads it;
+/// streaming between concurrent stages is out of scope.
+fn execute(commands: Vec<CommandPart>, shell: &mut ShellCore) -> i32 {
+    // Output of the previous stage when it was piped into the current one
+    let mut piped: Option<Vec<u8>> = None;
+    let mut status = 0;
+
     for part in commands.iter() {
-        if is_built_in(&part.program) {
-            execute_built_in(&part.program, shell, &part.args);
-        } 
+        if let Some(err) = part.parse_error {
+            eprintln!("{}: {}", part.program, err);
+            return 2;
+        }
+
+        let input = piped.take();
+        if !is_built_in(&part.program) {
+            eprintln!("{}: command not found", part.program);
+            status = 127;
+            continue;
+        }
+
+        if part.direction == Some(Direction::Pipe) {
+            let mut buffer: Vec<u8> = Vec::new();
+            status = execute_built_in(&part.program, shell, &part.args, input.as_deref(), &mut buffer);
+            piped = Some(buffer);
+        } else {
+            let stdout = stdout();
+            let mut handle = stdout.lock();
+            status = execute_built_in(&part.program, shell, &part.args, input.as_deref(), &mut handle);
+            handle.flush().ok();
+        }
     }
+    status
 }
 
 
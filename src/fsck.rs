@@ -0,0 +1,959 @@
+use std::collections::{BTreeMap, HashSet};
+use std::fmt;
+
+use crate::models::Volume;
+
+const EOC: u32 = 0x0FFFFFF8;
+const BAD_CLUSTER: u32 = 0x0FFFFFF7;
+
+/// Where a 32-byte directory entry lives on disk.
+#[derive(Clone, Copy)]
+pub struct EntryLocation {
+    pub cluster: u32,
+    pub offset: usize,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum ChainFault {
+    Loop,       // the chain comes back to a cluster it already visited
+    OutOfRange, // the chain points below 2 or past the last cluster
+    Free,       // the chain runs into a cluster marked free
+    Bad,        // the chain runs into a cluster marked bad
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Category {
+    CrossLinked,
+    LostClusters,
+    BadChain,
+    SizeMismatch,
+    DotEntries,
+    InvalidName,
+    OrphanLfn,
+    FatCopies,
+    ReservedEntries,
+}
+
+impl Category {
+    pub fn title(&self) -> &'static str {
+        match self {
+            Category::CrossLinked => "Cross-linked clusters",
+            Category::LostClusters => "Lost clusters",
+            Category::BadChain => "Broken cluster chains",
+            Category::SizeMismatch => "File sizes that disagree with their chains",
+            Category::DotEntries => "Bad '.' and '..' entries",
+            Category::InvalidName => "Invalid 8.3 names",
+            Category::OrphanLfn => "Orphaned long-name entries",
+            Category::FatCopies => "FAT copy mismatches",
+            Category::ReservedEntries => "Bad reserved FAT entries",
+        }
+    }
+}
+
+pub enum Problem {
+    /// Two chains claim the same clusters.
+    CrossLinked { first: String, second: String, second_start: u32, second_entry: Option<EntryLocation>, clusters: Vec<u32> },
+    /// Allocated clusters no file or directory references, grouped into chains.
+    LostClusters { clusters: Vec<u32> },
+    /// A chain that cannot be followed to an end-of-chain marker. `last` is the
+    /// last good cluster, or None when the entry's first cluster is already bad.
+    BadChain { path: String, entry: Option<EntryLocation>, last: Option<u32>, next: u32, fault: ChainFault },
+    SizeMismatch { path: String, entry: EntryLocation, size: u32, chain_clusters: u32 },
+    /// A directory whose "." or ".." entry is missing or points at the wrong cluster.
+    DotEntries { path: String, dir_cluster: u32, parent_cluster: u32, detail: &'static str },
+    InvalidName { path: String, raw: [u8; 11] },
+    /// Long-name slots that do not belong to the entry that follows them.
+    OrphanLfn { dir: String, slots: Vec<EntryLocation> },
+    /// FAT copy `copy` (1-based from the second FAT) differs from the first.
+    FatCopy { copy: u8, entries: u32 },
+    /// FAT[0] does not hold the media byte or FAT[1] is not an end-of-chain mark.
+    ReservedEntries { fat0: u32, fat1: u32 },
+}
+
+impl Problem {
+    pub fn category(&self) -> Category {
+        match self {
+            Problem::CrossLinked { .. } => Category::CrossLinked,
+            Problem::LostClusters { .. } => Category::LostClusters,
+            Problem::BadChain { .. } => Category::BadChain,
+            Problem::SizeMismatch { .. } => Category::SizeMismatch,
+            Problem::DotEntries { .. } => Category::DotEntries,
+            Problem::InvalidName { .. } => Category::InvalidName,
+            Problem::OrphanLfn { .. } => Category::OrphanLfn,
+            Problem::FatCopy { .. } => Category::FatCopies,
+            Problem::ReservedEntries { .. } => Category::ReservedEntries,
+        }
+    }
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Problem::CrossLinked { first, second, clusters, .. } => {
+                write!(f, "{} and {} share {} cluster(s) starting at {}", first, second, clusters.len(), clusters[0])
+            }
+            Problem::LostClusters { clusters } => {
+                write!(f, "chain of {} cluster(s) starting at {}", clusters.len(), clusters[0])
+            }
+            Problem::BadChain { path, last, next, fault, .. } => {
+                let what = match fault {
+                    ChainFault::Loop => "loops back to",
+                    ChainFault::OutOfRange => "points out of range to",
+                    ChainFault::Free => "runs into free cluster",
+                    ChainFault::Bad => "runs into bad cluster",
+                };
+                match last {
+                    Some(last) => write!(f, "{}: cluster {} {} {}", path, last, what, next),
+                    None => write!(f, "{}: first cluster {} is invalid", path, next),
+                }
+            }
+            Problem::SizeMismatch { path, size, chain_clusters, .. } => {
+                write!(f, "{}: size {} bytes but chain has {} cluster(s)", path, size, chain_clusters)
+            }
+            Problem::DotEntries { path, detail, .. } => write!(f, "{}: {}", path, detail),
+            Problem::InvalidName { path, raw, .. } => {
+                write!(f, "{}: raw name {:?}", path, String::from_utf8_lossy(raw))
+            }
+            Problem::OrphanLfn { dir, slots } => {
+                write!(f, "{}: {} slot(s) at cluster {} offset {}", dir, slots.len(), slots[0].cluster, slots[0].offset)
+            }
+            Problem::FatCopy { copy, entries } => {
+                write!(f, "FAT #{} differs from FAT #1 in {} entries", copy + 1, entries)
+            }
+            Problem::ReservedEntries { fat0, fat1 } => {
+                write!(f, "FAT[0] = {:#010X}, FAT[1] = {:#010X}", fat0, fat1)
+            }
+        }
+    }
+}
+
+pub struct Report {
+    pub problems: Vec<Problem>,
+    pub directories: u32,
+    pub files: u32,
+    pub used_clusters: u32,
+}
+
+impl Report {
+    pub fn is_clean(&self) -> bool {
+        self.problems.is_empty()
+    }
+}
+
+/// Walks the whole tree from the root cluster and checks the FAT and every
+/// directory entry against each other. Nothing on disk is modified.
+pub fn check(vol: &mut Volume) -> std::io::Result<Report> {
+    let max_cluster = vol.max_cluster();
+    let mut checker = Checker {
+        owners: vec![0; max_cluster as usize + 1],
+        chains: Vec::new(),
+        cross_links: BTreeMap::new(),
+        max_cluster,
+        report: Report { problems: Vec::new(), directories: 0, files: 0, used_clusters: 0 },
+        vol,
+    };
+
+    checker.check_reserved_entries();
+    checker.walk_tree()?;
+    checker.report_cross_links();
+    checker.find_lost_clusters();
+    checker.compare_fat_copies()?;
+    Ok(checker.report)
+}
+
+struct Checker<'a> {
+    vol: &'a mut Volume,
+    max_cluster: u32,
+    owners: Vec<usize>, // chain index + 1 of the chain that claimed each cluster, 0 if none
+    chains: Vec<(String, u32, Option<EntryLocation>)>, // path, first cluster and entry of each claimed chain
+    cross_links: BTreeMap<(usize, usize), Vec<u32>>,
+    report: Report,
+}
+
+struct PendingDir {
+    path: String,
+    cluster: u32,
+    parent: u32,
+    clusters: Vec<u32>,
+}
+
+struct LfnRun {
+    checksum: u8,
+    next_ord: u8,
+    slots: Vec<EntryLocation>,
+}
+
+impl Checker<'_> {
+    /// FAT[0] must be the media byte with every other bit set, and FAT[1] an
+    /// end-of-chain mark; its top two bits are the dirty and error flags.
+    fn check_reserved_entries(&mut self) {
+        let fat0 = self.vol.fat[0];
+        let fat1 = self.vol.fat[1];
+        if fat0 & 0x0FFFFFFF != reserved_fat0(self.vol.bpb.bpb_media) || fat1 & 0x03FFFFFF < 0x03FFFFF8 {
+            self.report.problems.push(Problem::ReservedEntries { fat0, fat1 });
+        }
+    }
+
+    fn walk_tree(&mut self) -> std::io::Result<()> {
+        let root = self.vol.bpb.bpb_root_clus;
+        let root_clusters = self.claim_chain("/", root, None);
+        let mut stack = vec![PendingDir { path: String::from("/"), cluster: root, parent: 0, clusters: root_clusters }];
+
+        while let Some(dir) = stack.pop() {
+            self.report.directories += 1;
+            let subdirs = self.check_directory(&dir)?;
+            stack.extend(subdirs.into_iter().rev());
+        }
+        Ok(())
+    }
+
+    /// Checks every entry of one directory and returns its subdirectories.
+    fn check_directory(&mut self, dir: &PendingDir) -> std::io::Result<Vec<PendingDir>> {
+        let root = self.vol.bpb.bpb_root_clus;
+        let bytes_per_cluster = self.vol.bpb.bpb_byts_per_sec as usize * self.vol.bpb.bpb_sec_per_clus as usize;
+        let mut subdirs = Vec::new();
+        let mut lfn: Option<LfnRun> = None;
+        let mut index = 0usize;
+
+        'clusters: for &cluster in dir.clusters.iter() {
+            let buf = self.vol.read_cluster(cluster)?;
+
+            for offset in (0..bytes_per_cluster).step_by(32) {
+                let entry = &buf[offset..offset + 32];
+                let location = EntryLocation { cluster, offset };
+                index += 1;
+
+                // The dot slots are checked before anything ends the walk, so
+                // a directory that is empty or deleted there is still reported
+                if dir.cluster != root && index <= 2 {
+                    self.check_dot_entry(dir, entry, index);
+                }
+                if entry[0] == 0x00 {
+                    if dir.cluster != root && index == 1 {
+                        self.check_dot_entry(dir, entry, 2);
+                    }
+                    break 'clusters;
+                }
+                if entry[0] == 0xE5 {
+                    if let Some(run) = lfn.take() {
+                        self.orphan(&dir.path, run.slots);
+                    }
+                    continue;
+                }
+                if entry[11] == 0x0F {
+                    self.track_lfn_slot(&dir.path, entry, location, &mut lfn);
+                    continue;
+                }
+                if let Some(run) = lfn.take()
+                    && (run.next_ord != 0 || run.checksum != Volume::lfn_checksum(&entry[0..11]))
+                {
+                    self.orphan(&dir.path, run.slots);
+                }
+
+                // Only real dot entries are skipped; anything else in their
+                // slots is reported and then checked like any other entry
+                if dir.cluster != root {
+                    let is_dot = &entry[0..11] == b".          " || &entry[0..11] == b"..         ";
+                    if index > 2 && is_dot {
+                        self.report.problems.push(Problem::DotEntries {
+                            path: dir.path.clone(),
+                            dir_cluster: dir.cluster,
+                            parent_cluster: dir.parent,
+                            detail: "'.' or '..' entry out of place",
+                        });
+                    }
+                    if is_dot {
+                        continue;
+                    }
+                }
+                if (entry[11] & 0x08) != 0 {
+                    continue; // volume label
+                }
+
+                let name = self.vol.parse_short_name(&entry[0..11]);
+                let path = if dir.path == "/" { format!("/{}", name) } else { format!("{}/{}", dir.path, name) };
+                if !valid_short_name(&entry[0..11]) {
+                    let mut raw = [0u8; 11];
+                    raw.copy_from_slice(&entry[0..11]);
+                    self.report.problems.push(Problem::InvalidName { path: path.clone(), raw });
+                }
+
+                let hi = u16::from_le_bytes([entry[20], entry[21]]) as u32;
+                let lo = u16::from_le_bytes([entry[26], entry[27]]) as u32;
+                let start = (hi << 16) | lo;
+                let size = u32::from_le_bytes([entry[28], entry[29], entry[30], entry[31]]);
+
+                if (entry[11] & 0x10) != 0 {
+                    // A directory already claimed by another chain is reported
+                    // as cross-linked but not descended into a second time
+                    let already_seen = start >= 2 && start <= self.max_cluster && self.owners[start as usize] != 0;
+                    let clusters = self.claim_chain(&path, start, Some(location));
+                    if !already_seen && !clusters.is_empty() {
+                        subdirs.push(PendingDir { path, cluster: start, parent: dir.cluster, clusters });
+                    }
+                } else {
+                    self.report.files += 1;
+                    let clusters = if start == 0 { Vec::new() } else { self.claim_chain(&path, start, Some(location)) };
+                    let expected = (size as usize).div_ceil(bytes_per_cluster);
+                    if clusters.len() != expected {
+                        self.report.problems.push(Problem::SizeMismatch {
+                            path,
+                            entry: location,
+                            size,
+                            chain_clusters: clusters.len() as u32,
+                        });
+                    }
+                }
+            }
+        }
+
+        if let Some(run) = lfn.take() {
+            self.orphan(&dir.path, run.slots);
+        }
+        Ok(subdirs)
+    }
+
+    fn check_dot_entry(&mut self, dir: &PendingDir, entry: &[u8], index: usize) {
+        let root = self.vol.bpb.bpb_root_clus;
+        let hi = u16::from_le_bytes([entry[20], entry[21]]) as u32;
+        let lo = u16::from_le_bytes([entry[26], entry[27]]) as u32;
+        let points_to = (hi << 16) | lo;
+        let is_dir = (entry[11] & 0x10) != 0;
+
+        let missing = entry[0] == 0x00 || entry[0] == 0xE5;
+        let detail = if index == 1 {
+            if missing {
+                Some("'.' entry is missing")
+            } else if &entry[0..11] != b".          " || !is_dir {
+                Some("first entry is not '.'")
+            } else if points_to != dir.cluster {
+                Some("'.' does not point to the directory itself")
+            } else {
+                None
+            }
+        } else {
+            let expected = if dir.parent == root { 0 } else { dir.parent };
+            if missing {
+                Some("'..' entry is missing")
+            } else if &entry[0..11] != b"..         " || !is_dir {
+                Some("second entry is not '..'")
+            } else if points_to != expected {
+                Some("'..' does not point to the parent directory")
+            } else {
+                None
+            }
+        };
+
+        if let Some(detail) = detail {
+            self.report.problems.push(Problem::DotEntries {
+                path: dir.path.clone(),
+                dir_cluster: dir.cluster,
+                parent_cluster: dir.parent,
+                detail,
+            });
+        }
+    }
+
+    fn track_lfn_slot(&mut self, dir_path: &str, entry: &[u8], location: EntryLocation, lfn: &mut Option<LfnRun>) {
+        let ord = entry[0] & 0x1F;
+        let checksum = entry[13];
+
+        if (entry[0] & 0x40) != 0 {
+            if let Some(run) = lfn.take() {
+                self.orphan(dir_path, run.slots);
+            }
+            if ord == 0 {
+                self.orphan(dir_path, vec![location]);
+            } else {
+                *lfn = Some(LfnRun { checksum, next_ord: ord - 1, slots: vec![location] });
+            }
+            return;
+        }
+
+        match lfn.as_mut() {
+            Some(run) if run.next_ord == ord && ord >= 1 && run.checksum == checksum => {
+                run.next_ord -= 1;
+                run.slots.push(location);
+            }
+            _ => {
+                let mut slots = lfn.take().map(|run| run.slots).unwrap_or_default();
+                slots.push(location);
+                self.orphan(dir_path, slots);
+            }
+        }
+    }
+
+    fn orphan(&mut self, dir: &str, slots: Vec<EntryLocation>) {
+        self.report.problems.push(Problem::OrphanLfn { dir: dir.to_string(), slots });
+    }
+
+    /// Follows a chain from `start`, claiming every cluster on it for `path`.
+    /// Returns the clusters up to the first fault.
+    fn claim_chain(&mut self, path: &str, start: u32, entry: Option<EntryLocation>) -> Vec<u32> {
+        self.chains.push((path.to_string(), start, entry));
+        let chain_id = self.chains.len();
+
+        let mut clusters = Vec::new();
+        let mut visited = HashSet::new();
+        let mut last: Option<u32> = None;
+        let mut cur = start;
+
+        loop {
+            let fault = if cur < 2 || cur > self.max_cluster {
+                Some(ChainFault::OutOfRange)
+            } else if visited.contains(&cur) {
+                Some(ChainFault::Loop)
+            } else if self.vol.fat[cur as usize] & 0x0FFFFFFF == 0 {
+                Some(ChainFault::Free)
+            } else if self.vol.fat[cur as usize] & 0x0FFFFFFF == BAD_CLUSTER {
+                Some(ChainFault::Bad)
+            } else {
+                None
+            };
+            if let Some(fault) = fault {
+                self.report.problems.push(Problem::BadChain { path: path.to_string(), entry, last, next: cur, fault });
+                break;
+            }
+
+            visited.insert(cur);
+            clusters.push(cur);
+            match self.owners[cur as usize] {
+                0 => {
+                    self.owners[cur as usize] = chain_id;
+                    self.report.used_clusters += 1;
+                }
+                owner => self.cross_links.entry((owner, chain_id)).or_default().push(cur),
+            }
+
+            let next = self.vol.fat[cur as usize] & 0x0FFFFFFF;
+            if next >= EOC {
+                break;
+            }
+            last = Some(cur);
+            cur = next;
+        }
+
+        clusters
+    }
+
+    fn report_cross_links(&mut self) {
+        let cross_links = std::mem::take(&mut self.cross_links);
+        for ((first, second), clusters) in cross_links {
+            let (second_path, second_start, second_entry) = &self.chains[second - 1];
+            self.report.problems.push(Problem::CrossLinked {
+                first: self.chains[first - 1].0.clone(),
+                second: second_path.clone(),
+                second_start: *second_start,
+                second_entry: *second_entry,
+                clusters,
+            });
+        }
+    }
+
+    fn find_lost_clusters(&mut self) {
+        let is_lost = |c: u32, checker: &Self| {
+            let value = checker.vol.fat[c as usize] & 0x0FFFFFFF;
+            value != 0 && value != BAD_CLUSTER && checker.owners[c as usize] == 0
+        };
+
+        // Heads are lost clusters no other lost cluster points to
+        let mut pointed_to = HashSet::new();
+        for c in 2..=self.max_cluster {
+            if is_lost(c, self) {
+                pointed_to.insert(self.vol.fat[c as usize] & 0x0FFFFFFF);
+            }
+        }
+
+        let mut seen = HashSet::new();
+        let mut heads: Vec<u32> = (2..=self.max_cluster).filter(|&c| is_lost(c, self) && !pointed_to.contains(&c)).collect();
+        // Whatever is left afterwards sits on a loop of lost clusters
+        heads.extend((2..=self.max_cluster).filter(|&c| is_lost(c, self)));
+
+        for head in heads {
+            if seen.contains(&head) {
+                continue;
+            }
+            let mut clusters = Vec::new();
+            let mut cur = head;
+            while cur >= 2 && cur <= self.max_cluster && is_lost(cur, self) && seen.insert(cur) {
+                clusters.push(cur);
+                cur = self.vol.fat[cur as usize] & 0x0FFFFFFF;
+            }
+            self.report.problems.push(Problem::LostClusters { clusters });
+        }
+    }
+
+    fn compare_fat_copies(&mut self) -> std::io::Result<()> {
+        let bytes_per_sector = self.vol.bpb.bpb_byts_per_sec as usize;
+        let fat_sectors = self.vol.bpb.bpb_fatsz32;
+        let mut first = vec![0u8; bytes_per_sector];
+        let mut other = vec![0u8; bytes_per_sector];
+
+        for copy in 1..self.vol.bpb.bpb_num_fats {
+            let mut entries = 0;
+            for sector in 0..fat_sectors {
+                self.vol.read_sector(self.vol.first_fat_sector + sector, &mut first)?;
+                self.vol.read_sector(self.vol.first_fat_sector + copy as u32 * fat_sectors + sector, &mut other)?;
+                entries += first
+                    .chunks_exact(4)
+                    .zip(other.chunks_exact(4))
+                    .filter(|(a, b)| a != b)
+                    .count() as u32;
+            }
+            if entries > 0 {
+                self.report.problems.push(Problem::FatCopy { copy, entries });
+            }
+        }
+        Ok(())
+    }
+}
+
+/// One change to the volume that fixes a problem from the report.
+pub enum Repair {
+    /// Ends a broken chain after its last good cluster, or drops the first
+    /// cluster of an entry whose chain is broken from the start.
+    TerminateChain { path: String, entry: Option<EntryLocation>, last: Option<u32> },
+    /// Gives the second owner of cross-linked clusters its own copies of them.
+    CopyShared { path: String, start: u32, entry: Option<EntryLocation>, clusters: Vec<u32> },
+    FreeLost { clusters: Vec<u32> },
+    SaveLost { clusters: Vec<u32> },
+    SetSize { path: String, entry: EntryLocation, size: u32 },
+    RebuildDots { path: String, dir_cluster: u32, parent_cluster: u32 },
+    DropLfn { dir: String, slots: Vec<EntryLocation> },
+    ResyncFats,
+    ResetReserved,
+}
+
+impl Repair {
+    /// Chain repairs change which clusters each file owns, so the lost cluster
+    /// and size results have to be checked again before they are acted on.
+    pub fn is_structural(&self) -> bool {
+        matches!(self, Repair::TerminateChain { .. } | Repair::CopyShared { .. })
+    }
+}
+
+impl fmt::Display for Repair {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Repair::TerminateChain { path, last: Some(last), .. } => {
+                write!(f, "{}: end the chain at cluster {}", path, last)
+            }
+            Repair::TerminateChain { path, last: None, .. } => {
+                write!(f, "{}: drop the invalid first cluster", path)
+            }
+            Repair::CopyShared { path, clusters, .. } => {
+                write!(f, "{}: copy {} shared cluster(s) into a chain of its own", path, clusters.len())
+            }
+            Repair::FreeLost { clusters } => {
+                write!(f, "free {} lost cluster(s) starting at {}", clusters.len(), clusters[0])
+            }
+            Repair::SaveLost { clusters } => {
+                write!(f, "save {} lost cluster(s) starting at {} under /FOUND.000", clusters.len(), clusters[0])
+            }
+            Repair::SetSize { path, size, .. } => write!(f, "{}: set the size to {} bytes", path, size),
+            Repair::RebuildDots { path, .. } => write!(f, "{}: rebuild the '.' and '..' entries", path),
+            Repair::DropLfn { dir, slots } => {
+                write!(f, "{}: drop {} orphaned long-name slot(s)", dir, slots.len())
+            }
+            Repair::ResyncFats => write!(f, "copy FAT #1 over the other FAT copies"),
+            Repair::ResetReserved => write!(f, "reset FAT[0] and FAT[1]"),
+        }
+    }
+}
+
+/// Turns the problems in a report into the repairs that fix them. Invalid
+/// names are left alone because there is no safe name to replace them with.
+pub fn plan(report: &Report, bytes_per_cluster: u32, save_lost: bool) -> Vec<Repair> {
+    let mut repairs: Vec<Repair> = Vec::new();
+
+    for problem in &report.problems {
+        match problem {
+            Problem::BadChain { path, entry, last, .. } => {
+                // The root has no entry to clear, so a root chain broken from
+                // its first cluster cannot be repaired here
+                if last.is_some() || entry.is_some() {
+                    repairs.push(Repair::TerminateChain { path: path.clone(), entry: *entry, last: *last });
+                }
+            }
+            Problem::CrossLinked { second, second_start, second_entry, clusters, .. } => {
+                // A chain cross-linked with several others is copied in one go
+                let existing = repairs.iter_mut().find_map(|r| match r {
+                    Repair::CopyShared { start, clusters, .. } if start == second_start => Some(clusters),
+                    _ => None,
+                });
+                match existing {
+                    Some(shared) => shared.extend(clusters),
+                    None => repairs.push(Repair::CopyShared {
+                        path: second.clone(),
+                        start: *second_start,
+                        entry: *second_entry,
+                        clusters: clusters.clone(),
+                    }),
+                }
+            }
+            Problem::LostClusters { clusters } => {
+                let clusters = clusters.clone();
+                repairs.push(if save_lost { Repair::SaveLost { clusters } } else { Repair::FreeLost { clusters } });
+            }
+            Problem::SizeMismatch { path, entry, chain_clusters, .. } => {
+                repairs.push(Repair::SetSize {
+                    path: path.clone(),
+                    entry: *entry,
+                    size: chain_clusters.saturating_mul(bytes_per_cluster),
+                });
+            }
+            Problem::DotEntries { path, dir_cluster, parent_cluster, .. } => {
+                let planned = repairs
+                    .iter()
+                    .any(|r| matches!(r, Repair::RebuildDots { dir_cluster: c, .. } if c == dir_cluster));
+                if !planned {
+                    repairs.push(Repair::RebuildDots {
+                        path: path.clone(),
+                        dir_cluster: *dir_cluster,
+                        parent_cluster: *parent_cluster,
+                    });
+                }
+            }
+            Problem::OrphanLfn { dir, slots } => {
+                repairs.push(Repair::DropLfn { dir: dir.clone(), slots: slots.clone() });
+            }
+            Problem::FatCopy { .. } => {
+                if !repairs.iter().any(|r| matches!(r, Repair::ResyncFats)) {
+                    repairs.push(Repair::ResyncFats);
+                }
+            }
+            Problem::ReservedEntries { .. } => repairs.push(Repair::ResetReserved),
+            Problem::InvalidName { .. } => {}
+        }
+    }
+    repairs
+}
+
+/// Applies repairs in order and writes the FAT back to every copy.
+pub fn apply(vol: &mut Volume, repairs: &[Repair]) -> std::io::Result<()> {
+    let mut found_dir: Option<u32> = None;
+
+    for repair in repairs {
+        match repair {
+            Repair::TerminateChain { last: Some(last), .. } => vol.fat[*last as usize] = 0x0FFFFFFF,
+            Repair::TerminateChain { entry: Some(entry), last: None, .. } => {
+                let mut raw = vol.read_raw_entry(entry.cluster, entry.offset)?;
+                if (raw[11] & 0x10) != 0 {
+                    // A directory with no valid cluster has nothing left to keep
+                    raw[0] = 0xE5;
+                } else {
+                    raw[20..22].fill(0);
+                    raw[26..28].fill(0);
+                    raw[28..32].fill(0);
+                }
+                vol.write_raw_entry(entry.cluster, entry.offset, &raw)?;
+            }
+            Repair::TerminateChain { .. } => {}
+            Repair::CopyShared { start, entry, clusters, .. } => copy_shared(vol, *start, *entry, clusters)?,
+            Repair::FreeLost { clusters } => {
+                for &c in clusters {
+                    vol.fat[c as usize] = 0;
+                }
+            }
+            Repair::SaveLost { clusters } => {
+                let dir = match found_dir {
+                    Some(dir) => dir,
+                    None => found_directory(vol)?,
+                };
+                found_dir = Some(dir);
+
+                let name = (0..10000)
+                    .map(|n| format!("FILE{:04}.CHK", n))
+                    .find(|name| vol.find_entry_in_directory(dir, name).is_none())
+                    .ok_or_else(|| std::io::Error::other("FOUND.000 is full"))?;
+                // Lost chains can end anywhere, including back on themselves
+                vol.fat[*clusters.last().unwrap() as usize] = 0x0FFFFFFF;
+                let bytes_per_cluster = vol.bpb.bpb_byts_per_sec as u32 * vol.bpb.bpb_sec_per_clus as u32;
+                vol.add_directory_entry(dir, &name, 0x20, clusters[0], clusters.len() as u32 * bytes_per_cluster)?;
+            }
+            Repair::SetSize { entry, size, .. } => {
+                let mut raw = vol.read_raw_entry(entry.cluster, entry.offset)?;
+                raw[28..32].copy_from_slice(&size.to_le_bytes());
+                vol.write_raw_entry(entry.cluster, entry.offset, &raw)?;
+            }
+            Repair::RebuildDots { dir_cluster, parent_cluster, .. } => {
+                let root = vol.bpb.bpb_root_clus;
+                let parent = if *parent_cluster == root { 0 } else { *parent_cluster };
+                for (offset, name, target) in [(0, ".", *dir_cluster), (32, "..", parent)] {
+                    let raw = vol.read_raw_entry(*dir_cluster, offset)?;
+                    // A real entry sitting where a dot entry should be is moved
+                    // to a free slot first, never overwritten
+                    let live = raw[0] != 0x00 && raw[0] != 0xE5 && raw[0] != b'.';
+                    if live {
+                        let (cluster, free) = vol.free_entry_slot(*dir_cluster)?;
+                        vol.write_raw_entry(cluster, free, &raw)?;
+                    }
+                    let mut entry = [0u8; 32];
+                    vol.write_directory_entry(&mut entry, name, 0x10, target, 0);
+                    vol.write_raw_entry(*dir_cluster, offset, &entry)?;
+                }
+            }
+            Repair::DropLfn { slots, .. } => {
+                for slot in slots {
+                    vol.mark_entry_deleted(slot.cluster, slot.offset)?;
+                }
+            }
+            // The FAT is written to every copy below
+            Repair::ResyncFats => {}
+            Repair::ResetReserved => {
+                vol.fat[0] = reserved_fat0(vol.bpb.bpb_media);
+                vol.fat[1] = 0x0FFFFFFF;
+            }
+        }
+    }
+
+    vol.flush_fat()
+}
+
+/// Walks the chain starting at `start` and replaces each shared cluster with
+/// a fresh copy, leaving the clusters themselves to their first owner.
+fn copy_shared(vol: &mut Volume, start: u32, entry: Option<EntryLocation>, shared: &[u32]) -> std::io::Result<()> {
+    let max_cluster = vol.max_cluster();
+    let mut visited = HashSet::new();
+    let mut prev: Option<u32> = None;
+    let mut cur = start;
+
+    while cur >= 2 && cur <= max_cluster && visited.insert(cur) {
+        let next = vol.fat[cur as usize] & 0x0FFFFFFF;
+
+        let target = if shared.contains(&cur) {
+            let copy = vol.alloc_cluster().ok_or_else(|| {
+                std::io::Error::new(std::io::ErrorKind::StorageFull, "no free clusters left to copy cross-linked clusters")
+            })?;
+            let data = vol.read_cluster(cur)?;
+            vol.write_data_cluster(copy, &data)?;
+            copy
+        } else {
+            cur
+        };
+
+        match prev {
+            Some(p) => vol.fat[p as usize] = target,
+            None if target != cur => {
+                if let Some(entry) = entry {
+                    let mut raw = vol.read_raw_entry(entry.cluster, entry.offset)?;
+                    raw[20..22].copy_from_slice(&((target >> 16) as u16).to_le_bytes());
+                    raw[26..28].copy_from_slice(&(target as u16).to_le_bytes());
+                    vol.write_raw_entry(entry.cluster, entry.offset, &raw)?;
+                }
+            }
+            None => {}
+        }
+
+        if next >= EOC {
+            break;
+        }
+        prev = Some(target);
+        cur = next;
+    }
+    Ok(())
+}
+
+/// Finds /FOUND.000, creating it when it does not exist yet.
+fn found_directory(vol: &mut Volume) -> std::io::Result<u32> {
+    let root = vol.bpb.bpb_root_clus;
+    if let Some((cluster, offset)) = vol.find_entry_in_directory(root, "FOUND.000") {
+        let raw = vol.read_raw_entry(cluster, offset)?;
+        let hi = u16::from_le_bytes([raw[20], raw[21]]) as u32;
+        let lo = u16::from_le_bytes([raw[26], raw[27]]) as u32;
+        return Ok((hi << 16) | lo);
+    }
+
+    let dir = vol.alloc_cluster().ok_or_else(|| {
+        std::io::Error::new(std::io::ErrorKind::StorageFull, "no free clusters left for FOUND.000")
+    })?;
+    vol.initialize_directory_cluster(dir, root);
+    vol.add_directory_entry(root, "FOUND.000", 0x10, dir, 0)?;
+    Ok(dir)
+}
+
+/// The value FAT[0] should hold for a volume with `media` as its media byte.
+fn reserved_fat0(media: u8) -> u32 {
+    0x0FFFFF00 | media as u32
+}
+
+/// Checks the 11 raw bytes of a short name against the characters FAT allows.
+pub fn valid_short_name(raw: &[u8]) -> bool {
+    if raw[0] == b' ' {
+        return false;
+    }
+    raw[..11].iter().enumerate().all(|(i, &b)| {
+        if i == 0 && b == 0x05 {
+            return true; // stands in for a leading 0xE5
+        }
+        b >= 0x20 && !b.is_ascii_lowercase() && !b"\"*+,./:;<=>?[\\]|".contains(&b)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::{self, OpenOptions};
+    use std::path::PathBuf;
+
+    use super::*;
+    use crate::models::BootSector;
+
+    /// A freshly formatted image, deleted again when dropped.
+    struct Scratch {
+        path: PathBuf,
+        vol: Volume,
+    }
+
+    impl Drop for Scratch {
+        fn drop(&mut self) {
+            fs::remove_file(&self.path).ok();
+        }
+    }
+
+    fn scratch(name: &str) -> Scratch {
+        let path = std::env::temp_dir().join(format!("rust_fat32_fsck_{}_{}.img", std::process::id(), name));
+        let args = [path.to_string_lossy().into_owned(), String::from("--size"), String::from("64M")];
+        assert_eq!(crate::mkfs::mkfs(&args), 0);
+        let mut file = OpenOptions::new().read(true).write(true).open(&path).unwrap();
+        let bpb = BootSector::new(&mut file);
+        Scratch { path, vol: Volume::new(file, bpb) }
+    }
+
+    fn bytes_per_cluster(vol: &Volume) -> u32 {
+        vol.bpb.bpb_byts_per_sec as u32 * vol.bpb.bpb_sec_per_clus as u32
+    }
+
+    /// Adds a file to the root whose chain is `clusters` long and returns the chain.
+    fn add_file(vol: &mut Volume, name: &str, clusters: u32) -> Vec<u32> {
+        let chain: Vec<u32> = (0..clusters).map(|_| vol.alloc_cluster().unwrap()).collect();
+        for pair in chain.windows(2) {
+            vol.fat[pair[0] as usize] = pair[1];
+        }
+        let root = vol.bpb.bpb_root_clus;
+        let size = clusters * bytes_per_cluster(vol);
+        vol.add_directory_entry(root, name, 0x20, chain[0], size).unwrap();
+        vol.flush_fat().unwrap();
+        chain
+    }
+
+    /// Adds an empty directory to the root and returns its cluster.
+    fn add_dir(vol: &mut Volume, name: &str) -> u32 {
+        let root = vol.bpb.bpb_root_clus;
+        let cluster = vol.alloc_cluster().unwrap();
+        vol.initialize_directory_cluster(cluster, root);
+        vol.add_directory_entry(root, name, 0x10, cluster, 0).unwrap();
+        vol.flush_fat().unwrap();
+        cluster
+    }
+
+    fn has(report: &Report, category: Category) -> bool {
+        report.problems.iter().any(|p| p.category() == category)
+    }
+
+    /// Repairs the volume the way `fsck --repair` does and checks it again.
+    fn repair(vol: &mut Volume) -> Report {
+        let bytes_per_cluster = bytes_per_cluster(vol);
+        for _ in 0..4 {
+            let report = check(vol).unwrap();
+            let mut repairs = plan(&report, bytes_per_cluster, false);
+            if repairs.iter().any(|r| r.is_structural()) {
+                repairs.retain(|r| r.is_structural());
+            }
+            if repairs.is_empty() {
+                return report;
+            }
+            apply(vol, &repairs).unwrap();
+        }
+        check(vol).unwrap()
+    }
+
+    #[test]
+    fn files_and_directories_check_clean() {
+        let mut s = scratch("clean");
+        add_file(&mut s.vol, "A.TXT", 3);
+        add_dir(&mut s.vol, "D");
+
+        let report = check(&mut s.vol).unwrap();
+        assert!(report.is_clean());
+        assert_eq!((report.directories, report.files, report.used_clusters), (2, 1, 5));
+    }
+
+    #[test]
+    fn lost_cluster_is_freed() {
+        let mut s = scratch("lost");
+        let lost = s.vol.alloc_cluster().unwrap();
+        s.vol.flush_fat().unwrap();
+
+        assert!(has(&check(&mut s.vol).unwrap(), Category::LostClusters));
+        assert!(repair(&mut s.vol).is_clean());
+        assert_eq!(s.vol.fat[lost as usize], 0);
+    }
+
+    #[test]
+    fn cross_link_gets_its_own_copy() {
+        let mut s = scratch("cross");
+        let a = add_file(&mut s.vol, "A.TXT", 3);
+        let b = add_file(&mut s.vol, "B.TXT", 3);
+        // B now runs into the middle of A
+        s.vol.fat[b[0] as usize] = a[1];
+        s.vol.flush_fat().unwrap();
+
+        assert!(has(&check(&mut s.vol).unwrap(), Category::CrossLinked));
+        assert!(repair(&mut s.vol).is_clean());
+        let b_chain = s.vol.chain_clusters(b[0]);
+        assert_eq!(b_chain.len(), 3);
+        assert!(b_chain.iter().all(|c| !a.contains(c)));
+        assert_eq!(s.vol.chain_clusters(a[0]), a);
+    }
+
+    #[test]
+    fn deleted_dot_entry_is_rebuilt() {
+        let mut s = scratch("dot");
+        let dir = add_dir(&mut s.vol, "D");
+        s.vol.mark_entry_deleted(dir, 0).unwrap();
+
+        assert!(has(&check(&mut s.vol).unwrap(), Category::DotEntries));
+        assert!(repair(&mut s.vol).is_clean());
+        assert_eq!(&s.vol.read_raw_entry(dir, 0).unwrap()[0..11], b".          ");
+    }
+
+    #[test]
+    fn zeroed_directory_gets_dot_entries_back() {
+        let mut s = scratch("zeroed");
+        let dir = add_dir(&mut s.vol, "D");
+        let zeroes = vec![0u8; bytes_per_cluster(&s.vol) as usize];
+        s.vol.write_cluster(dir, &zeroes).unwrap();
+
+        let report = check(&mut s.vol).unwrap();
+        let dots = report.problems.iter().filter(|p| p.category() == Category::DotEntries).count();
+        assert_eq!(dots, 2);
+        assert!(repair(&mut s.vol).is_clean());
+        let root = s.vol.bpb.bpb_root_clus;
+        assert_eq!(s.vol.resolve_directory(root, "D/.."), Some(root));
+    }
+
+    #[test]
+    fn looped_chain_is_cut() {
+        let mut s = scratch("loop");
+        let a = add_file(&mut s.vol, "A.TXT", 3);
+        s.vol.fat[a[2] as usize] = a[0];
+        s.vol.flush_fat().unwrap();
+
+        let report = check(&mut s.vol).unwrap();
+        assert!(report.problems.iter().any(|p| matches!(p, Problem::BadChain { fault: ChainFault::Loop, .. })));
+        assert!(repair(&mut s.vol).is_clean());
+        assert_eq!(s.vol.chain_clusters(a[0]), a);
+        assert!(s.vol.fat[a[2] as usize] >= EOC);
+    }
+
+    #[test]
+    fn reserved_entries_are_reset() {
+        let mut s = scratch("reserved");
+        s.vol.fat[0] = 0;
+        s.vol.flush_fat().unwrap();
+
+        assert!(has(&check(&mut s.vol).unwrap(), Category::ReservedEntries));
+        assert!(repair(&mut s.vol).is_clean());
+        assert_eq!(s.vol.fat[0], 0x0FFFFFF8);
+    }
+}
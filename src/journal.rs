@@ -0,0 +1,241 @@
+use std::collections::BTreeMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+const MAGIC: &[u8; 8] = b"FATJRNL1";
+const COMMIT: &[u8; 8] = b"COMMITTD";
+
+/// Collects the metadata sectors one command writes and commits them to the
+/// image together. The sectors are first written to `IMAGE.journal` and
+/// synced; only then does the image change, and the journal is removed once
+/// the image is synced too. A crash before the commit record is on disk
+/// leaves the image as it was; a crash after it is finished by `recover`.
+pub struct Journal {
+    path: PathBuf,
+    pending: BTreeMap<u64, Vec<u8>>, // by byte offset in the image
+}
+
+/// What `recover` found next to the image.
+pub enum Recovery {
+    Clean,
+    Replayed(usize),
+    RolledBack,
+}
+
+impl Journal {
+    pub fn new(path: PathBuf) -> Self {
+        Journal { path, pending: BTreeMap::new() }
+    }
+
+    /// The sidecar journal for an image: the image's path with ".journal" added.
+    pub fn path_for(image: &str) -> PathBuf {
+        PathBuf::from(format!("{}.journal", image))
+    }
+
+    pub fn stage(&mut self, offset: u64, buf: &[u8]) {
+        self.pending.insert(offset, buf.to_vec());
+    }
+
+    /// The staged contents of the sector at `offset`, if this command wrote it.
+    pub fn staged(&self, offset: u64) -> Option<&[u8]> {
+        self.pending.get(&offset).map(|v| v.as_slice())
+    }
+
+    /// Drops a staged sector that is being overwritten directly.
+    pub fn unstage(&mut self, offset: u64) {
+        self.pending.remove(&offset);
+    }
+
+    /// Makes the staged sectors durable in the journal, applies them to the
+    /// image and clears the journal. Returns how many sectors were committed.
+    /// If the journal can't be written, the sectors stay staged and go out
+    /// with the next commit, matching the FAT already held in memory.
+    pub fn commit(&mut self, image: &mut File) -> io::Result<usize> {
+        if self.pending.is_empty() {
+            return Ok(0);
+        }
+
+        let mut journal = File::create(&self.path)?;
+        journal.write_all(&self.record())?;
+        journal.sync_all()?;
+
+        let count = self.pending.len();
+        for (offset, data) in std::mem::take(&mut self.pending) {
+            image.seek(SeekFrom::Start(offset))?;
+            image.write_all(&data)?;
+        }
+        image.sync_data()?;
+        fs::remove_file(&self.path)?;
+        Ok(count)
+    }
+
+    /// The staged sectors as one journal record: the magic, the sector count,
+    /// each sector with its offset and length, a checksum and the commit mark.
+    fn record(&self) -> Vec<u8> {
+        let mut record = Vec::new();
+        record.extend_from_slice(MAGIC);
+        record.extend_from_slice(&(self.pending.len() as u32).to_le_bytes());
+        for (offset, data) in &self.pending {
+            record.extend_from_slice(&offset.to_le_bytes());
+            record.extend_from_slice(&(data.len() as u32).to_le_bytes());
+            record.extend_from_slice(data);
+        }
+        record.extend_from_slice(&checksum(&record).to_le_bytes());
+        record.extend_from_slice(COMMIT);
+        record
+    }
+}
+
+/// Finishes or undoes whatever an interrupted session left in the journal at
+/// `path`. A complete record is replayed into the image; a torn one is
+/// dropped, since the image is only written after the record is complete.
+pub fn recover(image: &mut File, path: &Path) -> io::Result<Recovery> {
+    let mut raw = Vec::new();
+    match OpenOptions::new().read(true).open(path) {
+        Ok(mut f) => f.read_to_end(&mut raw)?,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Recovery::Clean),
+        Err(e) => return Err(e),
+    };
+
+    let Some(sectors) = parse(&raw) else {
+        fs::remove_file(path)?;
+        return Ok(Recovery::RolledBack);
+    };
+    for (offset, data) in &sectors {
+        image.seek(SeekFrom::Start(*offset))?;
+        image.write_all(data)?;
+    }
+    image.sync_data()?;
+    fs::remove_file(path)?;
+    // The boot sector is read from wherever the file was left
+    image.rewind()?;
+    Ok(Recovery::Replayed(sectors.len()))
+}
+
+/// Splits a journal record into its sectors, or None if it is incomplete or
+/// does not match its checksum.
+fn parse(raw: &[u8]) -> Option<Vec<(u64, &[u8])>> {
+    let body_end = raw.len().checked_sub(12)?;
+    if !raw.starts_with(MAGIC) || &raw[body_end + 4..] != COMMIT {
+        return None;
+    }
+    let stored = u32::from_le_bytes(raw[body_end..body_end + 4].try_into().ok()?);
+    if stored != checksum(&raw[..body_end]) {
+        return None;
+    }
+
+    let count = u32::from_le_bytes(raw.get(8..12)?.try_into().ok()?);
+    let mut sectors = Vec::new();
+    let mut pos = 12;
+    for _ in 0..count {
+        let offset = u64::from_le_bytes(raw.get(pos..pos + 8)?.try_into().ok()?);
+        let len = u32::from_le_bytes(raw.get(pos + 8..pos + 12)?.try_into().ok()?) as usize;
+        sectors.push((offset, raw.get(pos + 12..pos + 12 + len)?));
+        pos += 12 + len;
+    }
+    (pos == body_end).then_some(sectors)
+}
+
+/// FNV-1a, enough to tell a torn record from a complete one.
+fn checksum(bytes: &[u8]) -> u32 {
+    bytes.iter().fold(0x811C9DC5u32, |hash, &b| (hash ^ b as u32).wrapping_mul(0x01000193))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A zeroed 4K image and the journal path next to it, both removed when dropped.
+    struct Scratch {
+        image: PathBuf,
+        journal: PathBuf,
+    }
+
+    impl Drop for Scratch {
+        fn drop(&mut self) {
+            fs::remove_file(&self.image).ok();
+            fs::remove_file(&self.journal).ok();
+        }
+    }
+
+    fn scratch(name: &str) -> Scratch {
+        let image = std::env::temp_dir().join(format!("rust_fat32_journal_{}_{}.img", std::process::id(), name));
+        fs::write(&image, vec![0u8; 4096]).unwrap();
+        let journal = Journal::path_for(&image.to_string_lossy());
+        Scratch { image, journal }
+    }
+
+    fn open(path: &Path) -> File {
+        OpenOptions::new().read(true).write(true).open(path).unwrap()
+    }
+
+    fn contents(path: &Path) -> Vec<u8> {
+        let mut raw = Vec::new();
+        File::open(path).unwrap().read_to_end(&mut raw).unwrap();
+        raw
+    }
+
+    /// A journal holding two sectors, as a crash would leave it before the image changed.
+    fn two_sector_record(path: &Path) -> Vec<u8> {
+        let mut journal = Journal::new(path.to_path_buf());
+        journal.stage(512, &[0xAA; 512]);
+        journal.stage(1024, &[0xBB; 512]);
+        journal.record()
+    }
+
+    #[test]
+    fn commit_writes_the_image_and_removes_the_journal() {
+        let s = scratch("commit");
+        let mut journal = Journal::new(s.journal.clone());
+        journal.stage(512, &[0xAA; 512]);
+        assert_eq!(journal.staged(512), Some(&[0xAA; 512][..]));
+
+        assert_eq!(journal.commit(&mut open(&s.image)).unwrap(), 1);
+        assert!(!s.journal.exists());
+        assert!(journal.staged(512).is_none());
+        assert!(contents(&s.image)[512..1024].iter().all(|&b| b == 0xAA));
+    }
+
+    #[test]
+    fn missing_journal_is_clean() {
+        let s = scratch("clean");
+        assert!(matches!(recover(&mut open(&s.image), &s.journal).unwrap(), Recovery::Clean));
+    }
+
+    #[test]
+    fn complete_journal_is_replayed() {
+        let s = scratch("complete");
+        fs::write(&s.journal, two_sector_record(&s.journal)).unwrap();
+
+        assert!(matches!(recover(&mut open(&s.image), &s.journal).unwrap(), Recovery::Replayed(2)));
+        assert!(!s.journal.exists());
+        let image = contents(&s.image);
+        assert!(image[..512].iter().all(|&b| b == 0));
+        assert!(image[512..1024].iter().all(|&b| b == 0xAA));
+        assert!(image[1024..1536].iter().all(|&b| b == 0xBB));
+    }
+
+    #[test]
+    fn torn_journal_is_rolled_back() {
+        let s = scratch("torn");
+        let record = two_sector_record(&s.journal);
+        // Cut off inside the second sector, before the checksum and commit mark
+        fs::write(&s.journal, &record[..record.len() - 100]).unwrap();
+
+        assert!(matches!(recover(&mut open(&s.image), &s.journal).unwrap(), Recovery::RolledBack));
+        assert!(!s.journal.exists());
+        assert!(contents(&s.image).iter().all(|&b| b == 0));
+    }
+
+    #[test]
+    fn journal_with_a_bad_checksum_is_rolled_back() {
+        let s = scratch("checksum");
+        let mut record = two_sector_record(&s.journal);
+        record[100] ^= 0xFF;
+        fs::write(&s.journal, &record).unwrap();
+
+        assert!(matches!(recover(&mut open(&s.image), &s.journal).unwrap(), Recovery::RolledBack));
+        assert!(contents(&s.image).iter().all(|&b| b == 0));
+    }
+}
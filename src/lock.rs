@@ -0,0 +1,56 @@
+use std::fs::{self, File, TryLockError};
+use std::os::unix::fs::MetadataExt;
+
+/// Takes an advisory `flock` on the image for as long as `file` stays open:
+/// exclusive for a read-write session and shared for a read-only one, so two
+/// sessions never each load the FAT and flush over the other's changes.
+pub fn lock_image(file: &File, exclusive: bool) -> Result<(), String> {
+    let result = if exclusive { file.try_lock() } else { file.try_lock_shared() };
+    match result {
+        Ok(()) => Ok(()),
+        Err(TryLockError::WouldBlock) => Err(format!("it is in use by {}", holders(file))),
+        Err(TryLockError::Error(e)) => Err(format!("failed to lock it: {}", e)),
+    }
+}
+
+/// Describes the processes holding a lock on the file, as listed in
+/// /proc/locks, or says "another process" when they can't be found.
+fn holders(file: &File) -> String {
+    let Ok(meta) = file.metadata() else {
+        return String::from("another process");
+    };
+    // /proc/locks names a file as major:minor:inode, the device numbers in hex
+    let dev = meta.dev();
+    let major = ((dev >> 8) & 0xfff) | ((dev >> 32) & !0xfff);
+    let minor = (dev & 0xff) | ((dev >> 12) & !0xff);
+    let id = format!("{:02x}:{:02x}:{}", major, minor, meta.ino());
+
+    let locks = fs::read_to_string("/proc/locks").unwrap_or_default();
+    let mut found = Vec::new();
+    for line in locks.lines() {
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        // Waiting requests are listed with "->" and hold nothing yet
+        if fields.len() < 6 || fields[1] != "FLOCK" || fields[5] != id {
+            continue;
+        }
+        let (mode, pid) = (fields[3], fields[4]);
+        let command = fs::read(format!("/proc/{}/cmdline", pid))
+            .map(|raw| {
+                let parts: Vec<String> = raw
+                    .split(|&b| b == 0)
+                    .filter(|part| !part.is_empty())
+                    .map(|part| String::from_utf8_lossy(part).into_owned())
+                    .collect();
+                parts.join(" ")
+            })
+            .unwrap_or_default();
+        let kind = if mode == "WRITE" { "exclusive" } else { "shared" };
+        if command.is_empty() {
+            found.push(format!("pid {} ({} lock)", pid, kind));
+        } else {
+            found.push(format!("pid {} `{}` ({} lock)", pid, command, kind));
+        }
+    }
+
+    if found.is_empty() { String::from("another process") } else { found.join(", ") }
+}
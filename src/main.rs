@@ -1,7 +1,10 @@
-use std::env::{args};
-use std::fs::{OpenOptions};
-use std::io::{Write, stdin, stdout};
+use std::env::{self, args};
+use std::fs::{self, OpenOptions};
+use std::path::PathBuf;
+use std::io::{ErrorKind, IsTerminal, stdin};
 use crate::executor::execute_command;
+use crate::journal::{Journal, Recovery};
+use crate::overlay::Overlay;
 use crate::models::ShellCore;
 
 
@@ -11,53 +14,197 @@ mod parser;
 mod commands;
 mod executor;
 mod builtins;
-    
+mod editor;
+mod fsck;
+mod mkfs;
+mod lock;
+mod journal;
+mod overlay;
+
+const USAGE: &str = "Usage: rust_fat32 [-e] [--norc] [--read-only] [--no-lock] [--journal] [--overlay FILE] <image_name> [-c \"cmd; cmd\" | script_file]\n       rust_fat32 mkfs <image_name> --size SIZE [options]";
+const RC_FILE: &str = ".rust_fat32rc";
+
 fn main() {
     let args: Vec<String> = args().collect();
-    if args.len() > 2 || args.len() < 2 {
-        eprintln!("This shell takes exactly one argument: the image name.\nUsage: rust_fat32 <image_name>");
-        std::process::exit(1);
+
+    if args.get(1).map(|s| s.as_str()) == Some("mkfs") {
+        std::process::exit(mkfs::mkfs(&args[2..]));
     }
-    let image = match OpenOptions::new()
-        .read(true)
-        .write(true)
-        .open(&args[1])
-    {
+
+    let mut errexit = false;
+    let mut norc = false;
+    let mut read_only = false;
+    let mut no_lock = false;
+    let mut journaled = false;
+    let mut overlay_path: Option<String> = None;
+    let mut inline_commands: Option<String> = None;
+    let mut positional: Vec<&String> = Vec::new();
+
+    let mut args_iter = args.iter().skip(1);
+    while let Some(arg) = args_iter.next() {
+        match arg.as_str() {
+            "-e" => errexit = true,
+            "--norc" => norc = true,
+            "--read-only" => read_only = true,
+            "--no-lock" => no_lock = true,
+            "--journal" => journaled = true,
+            "--overlay" => match args_iter.next() {
+                Some(path) => overlay_path = Some(path.clone()),
+                None => {
+                    eprintln!("--overlay needs a file name.\n{}", USAGE);
+                    std::process::exit(2);
+                }
+            },
+            "-c" => match args_iter.next() {
+                Some(commands) => inline_commands = Some(commands.clone()),
+                None => {
+                    eprintln!("-c needs a command string.\n{}", USAGE);
+                    std::process::exit(2);
+                }
+            },
+            _ => positional.push(arg),
+        }
+    }
+    if positional.is_empty() || positional.len() > 2 || (inline_commands.is_some() && positional.len() > 1) {
+        eprintln!("This shell takes an image name and optionally a script or -c commands.\n{}", USAGE);
+        std::process::exit(2);
+    }
+
+    let image_name = positional[0];
+    let opened = match OpenOptions::new().read(true).write(!read_only).open(image_name) {
+        // Read-only media and files still mount, just without writes
+        Err(e) if !read_only && matches!(e.kind(), ErrorKind::PermissionDenied | ErrorKind::ReadOnlyFilesystem) => {
+            eprintln!("Cannot open '{}' for writing ({}); mounting it read-only", image_name, e);
+            read_only = true;
+            OpenOptions::new().read(true).open(image_name)
+        }
+        other => other,
+    };
+    let mut image = match opened {
         Ok(file) => file,
         Err(e) => {
-            eprintln!("Failed to open image file '{}': {}", args[1], e);
+            eprintln!("Failed to open image file '{}': {}", image_name, e);
             std::process::exit(1);
         }
     };
 
-    let mut shell = crate::models::ShellCore::new(image);
-    loop {
-        create_prompt(&shell);
+    if !no_lock && let Err(e) = lock::lock_image(&image, !read_only) {
+        eprintln!("Cannot open '{}': {}. Use --no-lock to open it anyway.", image_name, e);
+        std::process::exit(1);
+    }
 
-        let mut input: String = String::new();
-        stdin().read_line(&mut input).unwrap();
-        let command = input.trim();
+    // A journal left behind means the last session stopped mid-command
+    let journal_path = Journal::path_for(image_name);
+    if journal_path.exists() {
+        if read_only {
+            eprintln!(
+                "Warning: '{}' has an unfinished journal in '{}'; open it read-write to recover it",
+                image_name,
+                journal_path.display()
+            );
+        } else {
+            match journal::recover(&mut image, &journal_path) {
+                Ok(Recovery::Replayed(n)) => eprintln!("Replayed {} sectors from the journal of an interrupted command", n),
+                Ok(Recovery::RolledBack) => eprintln!("Discarded an incomplete journal; the interrupted command was rolled back"),
+                Ok(Recovery::Clean) => {}
+                Err(e) => {
+                    eprintln!("Failed to recover '{}' from '{}': {}", image_name, journal_path.display(), e);
+                    std::process::exit(1);
+                }
+            }
+        }
+    }
 
-        if command.is_empty() {
-            continue;
+    let mut shell = crate::models::ShellCore::new(image, read_only);
+    if journaled && !read_only {
+        shell.vol.journal = Some(Journal::new(journal_path));
+    }
+    if let Some(path) = overlay_path {
+        let sector_size = shell.vol.bpb.bpb_byts_per_sec as usize;
+        let overlay = match Overlay::open(path.as_ref(), shell.vol.bpb.bs_vol_id, sector_size) {
+            Ok(overlay) => overlay,
+            Err(e) => {
+                eprintln!("Failed to open overlay '{}': {}", path, e);
+                std::process::exit(1);
+            }
+        };
+        if !no_lock && let Err(e) = lock::lock_image(overlay.file(), true) {
+            eprintln!("Cannot open overlay '{}': {}. Use --no-lock to open it anyway.", path, e);
+            std::process::exit(1);
+        }
+        shell.vol.overlay = Some(overlay);
+        // An overlay kept from an earlier session may hold a newer FAT
+        if let Err(e) = shell.vol.reload() {
+            eprintln!("Failed to read '{}' through overlay '{}': {}", image_name, path, e);
+            std::process::exit(1);
         }
+    }
 
-        execute_command(command, &mut shell);
+    // Team-wide aliases and variables live in ~/.rust_fat32rc on the host
+    if !norc
+        && let Some(home) = env::var_os("HOME")
+        && let Ok(rc) = fs::read_to_string(PathBuf::from(home).join(RC_FILE))
+    {
+        run_script(&rc, &mut shell);
+    }
+    // -e only turns errexit on, so a `set -e` in the rc file stays in force
+    if errexit {
+        shell.errexit = true;
+    }
 
+    let status = if let Some(commands) = inline_commands {
+        run_script(&commands, &mut shell)
+    } else if let Some(script_path) = positional.get(1) {
+        match fs::read_to_string(script_path) {
+            Ok(script) => run_script(&script, &mut shell),
+            Err(e) => {
+                eprintln!("Failed to read script '{}': {}", script_path, e);
+                std::process::exit(1);
+            }
+        }
+    } else if stdin().is_terminal() {
+        editor::run_interactive(image_name, shell)
+    } else {
+        run_stdin(&mut shell)
+    };
 
-    }
+    std::process::exit(status);
 }
 
-fn create_prompt(shell: &ShellCore) {
+/// Runs each line of a script and returns the last status. Under `set -e` the
+/// executor exits as soon as an untested command fails.
+fn run_script(script: &str, shell: &mut ShellCore) -> i32 {
+    for line in script.lines() {
+        let command = line.trim();
+        if command.is_empty() || command.starts_with('#') {
+            continue;
+        }
+
+        execute_command(command, shell);
+    }
+    shell.last_status
+}
 
-    let image = args().nth(1).unwrap();
+/// Reads commands from piped stdin until EOF, without printing a prompt.
+fn run_stdin(shell: &mut ShellCore) -> i32 {
+    loop {
+        let mut input: String = String::new();
+        match stdin().read_line(&mut input) {
+            Ok(0) => break,
+            Ok(_) => {}
+            Err(e) => {
+                eprintln!("Failed to read input: {}", e);
+                return 1;
+            }
+        }
+        let command = input.trim();
 
-    let path = shell.cwd_path.clone();
+        if command.is_empty() || command.starts_with('#') {
+            continue;
+        }
 
-    print!("{}{}>",image, path);
-    match stdout().flush() {
-        Ok(res) => res,
-        Err(_) => print!("Error Flushing")
-    }    
+        execute_command(command, shell);
+    }
 
+    shell.last_status
 }
@@ -0,0 +1,373 @@
+use std::fs::OpenOptions;
+use std::io::{Seek, SeekFrom, Write};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const USAGE: &str =
+    "Usage: rust_fat32 mkfs <image> --size SIZE [--cluster-size SIZE] [--label NAME] [--fats N] [--sector-size N]";
+
+const RESERVED_SECTORS: u16 = 32;
+const FSINFO_SECTOR: u16 = 1;
+const BACKUP_BOOT_SECTOR: u16 = 6;
+const MEDIA_FIXED_DISK: u8 = 0xF8;
+
+// FAT32 needs at least this many clusters, anything less is read as FAT16
+const MIN_CLUSTERS: u32 = 65525;
+const MAX_CLUSTERS: u32 = 0x0FFFFFF5;
+
+/// Layout of a volume about to be formatted.
+struct Geometry {
+    bytes_per_sector: u16,
+    sectors_per_cluster: u8,
+    num_fats: u8,
+    total_sectors: u32,
+    fat_size: u32,
+    cluster_count: u32,
+}
+
+/// Formats a new image: `rust_fat32 mkfs <image> --size 256M ...`.
+pub fn mkfs(args: &[String]) -> i32 {
+    let mut image: Option<&str> = None;
+    let mut size: Option<u64> = None;
+    let mut cluster_size: Option<u64> = None;
+    let mut label: Option<String> = None;
+    let mut num_fats: u8 = 2;
+    let mut sector_size: u64 = 512;
+
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        if !arg.starts_with("--") {
+            if image.is_some() {
+                eprintln!("mkfs: more than one image given\n{}", USAGE);
+                return 2;
+            }
+            image = Some(arg);
+            continue;
+        }
+
+        let Some(value) = iter.next() else {
+            eprintln!("mkfs: {} needs a value\n{}", arg, USAGE);
+            return 2;
+        };
+        let valid = match arg.as_str() {
+            "--size" => {
+                size = parse_size(value);
+                size.is_some()
+            }
+            "--cluster-size" => {
+                cluster_size = parse_size(value);
+                cluster_size.is_some()
+            }
+            "--sector-size" => match parse_size(value) {
+                Some(v) => {
+                    sector_size = v;
+                    true
+                }
+                None => false,
+            },
+            "--fats" => match value.parse() {
+                Ok(v) => {
+                    num_fats = v;
+                    true
+                }
+                Err(_) => false,
+            },
+            "--label" => {
+                label = Some(value.to_ascii_uppercase());
+                true
+            }
+            _ => {
+                eprintln!("mkfs: unknown option: {}\n{}", arg, USAGE);
+                return 2;
+            }
+        };
+        if !valid {
+            eprintln!("mkfs: invalid value for {}: {}", arg, value);
+            return 2;
+        }
+    }
+
+    let (Some(image), Some(size)) = (image, size) else {
+        eprintln!("mkfs: an image name and --size are required\n{}", USAGE);
+        return 2;
+    };
+    if let Some(label) = &label
+        && !valid_label(label)
+    {
+        eprintln!("mkfs: invalid volume label: {}", label);
+        return 2;
+    }
+
+    let geometry = match plan_geometry(size, sector_size, cluster_size, num_fats) {
+        Ok(g) => g,
+        Err(e) => {
+            eprintln!("mkfs: {}", e);
+            return 1;
+        }
+    };
+
+    if let Err(e) = write_image(image, &geometry, label.as_deref()) {
+        eprintln!("mkfs: failed to write '{}': {}", image, e);
+        return 1;
+    }
+
+    let cluster_bytes = geometry.bytes_per_sector as u32 * geometry.sectors_per_cluster as u32;
+    println!(
+        "Formatted {}: {} sectors of {} bytes, {} clusters of {} bytes, {} FAT(s) of {} sectors",
+        image,
+        geometry.total_sectors,
+        geometry.bytes_per_sector,
+        geometry.cluster_count,
+        cluster_bytes,
+        geometry.num_fats,
+        geometry.fat_size
+    );
+    0
+}
+
+/// Parses a byte count with an optional K, M, G or T suffix (powers of 1024).
+fn parse_size(value: &str) -> Option<u64> {
+    let upper = value.to_ascii_uppercase();
+    let digits = upper.trim_end_matches('B');
+    let (number, multiplier) = match digits.chars().last()? {
+        'K' => (&digits[..digits.len() - 1], 1u64 << 10),
+        'M' => (&digits[..digits.len() - 1], 1 << 20),
+        'G' => (&digits[..digits.len() - 1], 1 << 30),
+        'T' => (&digits[..digits.len() - 1], 1 << 40),
+        _ => (digits, 1),
+    };
+    number.parse::<u64>().ok()?.checked_mul(multiplier)
+}
+
+/// Cluster size Microsoft's format picks for a FAT32 volume of this size.
+fn default_cluster_size(volume_bytes: u64) -> u64 {
+    const MB: u64 = 1 << 20;
+    const GB: u64 = 1 << 30;
+    match volume_bytes {
+        b if b <= 260 * MB => 512,
+        b if b <= 8 * GB => 4096,
+        b if b <= 16 * GB => 8192,
+        b if b <= 32 * GB => 16384,
+        _ => 32768,
+    }
+}
+
+fn plan_geometry(size: u64, sector_size: u64, cluster_size: Option<u64>, num_fats: u8) -> Result<Geometry, String> {
+    if ![512, 1024, 2048, 4096].contains(&sector_size) {
+        return Err(format!("sector size must be 512, 1024, 2048 or 4096, not {}", sector_size));
+    }
+    if !(1..=2).contains(&num_fats) {
+        return Err(format!("the number of FATs must be 1 or 2, not {}", num_fats));
+    }
+
+    let cluster_size = cluster_size.unwrap_or_else(|| default_cluster_size(size).max(sector_size));
+    if !cluster_size.is_power_of_two() || cluster_size < sector_size || cluster_size / sector_size > 128 {
+        return Err(format!(
+            "cluster size must be a power of two between {} and {} bytes, not {}",
+            sector_size,
+            sector_size * 128,
+            cluster_size
+        ));
+    }
+
+    let total_sectors = size / sector_size;
+    if total_sectors > u32::MAX as u64 {
+        return Err(format!("{} bytes is too large for FAT32", size));
+    }
+    let total_sectors = total_sectors as u32;
+    let sectors_per_cluster = (cluster_size / sector_size) as u32;
+
+    // The FAT has to describe every data cluster, and growing it shrinks the
+    // data area, so iterate until the size settles
+    let mut fat_size = 1u32;
+    let cluster_count = loop {
+        let fat_sectors = fat_size as u64 * num_fats as u64;
+        let data_sectors = (total_sectors as u64).saturating_sub(RESERVED_SECTORS as u64 + fat_sectors);
+        let clusters = data_sectors / sectors_per_cluster as u64;
+        let needed = ((clusters + 2) * 4).div_ceil(sector_size) as u32;
+        if needed <= fat_size {
+            break clusters;
+        }
+        fat_size = needed;
+    };
+
+    if cluster_count < MIN_CLUSTERS as u64 {
+        return Err(format!(
+            "{} clusters of {} bytes is a FAT16 cluster count; FAT32 needs at least {} (use a larger --size or a smaller --cluster-size)",
+            cluster_count, cluster_size, MIN_CLUSTERS
+        ));
+    }
+    if cluster_count > MAX_CLUSTERS as u64 {
+        return Err(format!(
+            "{} clusters is more than FAT32 can address (use a larger --cluster-size)",
+            cluster_count
+        ));
+    }
+
+    Ok(Geometry {
+        bytes_per_sector: sector_size as u16,
+        sectors_per_cluster: sectors_per_cluster as u8,
+        num_fats,
+        total_sectors,
+        fat_size,
+        cluster_count: cluster_count as u32,
+    })
+}
+
+/// Labels follow the 8.3 character rules but may contain spaces.
+pub fn valid_label(label: &str) -> bool {
+    !label.is_empty()
+        && label.len() <= 11
+        && !label.starts_with(' ')
+        && label
+            .bytes()
+            .all(|b| (0x20..0x7F).contains(&b) && !b"\"*+,./:;<=>?[\\]|".contains(&b))
+}
+
+/// A volume serial number derived from the current time, like DOS does.
+pub fn volume_serial() -> u32 {
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+    let secs = now.as_secs() as u32;
+    let nanos = now.subsec_nanos();
+    (secs.rotate_left(16) ^ nanos).wrapping_add(nanos >> 7)
+}
+
+/// Pads a label to the 11 bytes stored on disk. No label is stored as "NO NAME".
+pub fn label_bytes(label: Option<&str>) -> [u8; 11] {
+    let mut raw = *b"NO NAME    ";
+    if let Some(label) = label {
+        raw = [b' '; 11];
+        raw[..label.len()].copy_from_slice(label.as_bytes());
+    }
+    raw
+}
+
+fn boot_sector(g: &Geometry, label: Option<&str>) -> Vec<u8> {
+    let mut bs = vec![0u8; g.bytes_per_sector as usize];
+    bs[0..3].copy_from_slice(&[0xEB, 0x58, 0x90]);
+    bs[3..11].copy_from_slice(b"MSWIN4.1");
+    bs[11..13].copy_from_slice(&g.bytes_per_sector.to_le_bytes());
+    bs[13] = g.sectors_per_cluster;
+    bs[14..16].copy_from_slice(&RESERVED_SECTORS.to_le_bytes());
+    bs[16] = g.num_fats;
+    // Root entry count, 16-bit sector count and 16-bit FAT size stay 0 on FAT32
+    bs[21] = MEDIA_FIXED_DISK;
+    bs[24..26].copy_from_slice(&63u16.to_le_bytes()); // sectors per track
+    bs[26..28].copy_from_slice(&255u16.to_le_bytes()); // heads
+    bs[32..36].copy_from_slice(&g.total_sectors.to_le_bytes());
+
+    bs[36..40].copy_from_slice(&g.fat_size.to_le_bytes());
+    bs[44..48].copy_from_slice(&2u32.to_le_bytes()); // root cluster
+    bs[48..50].copy_from_slice(&FSINFO_SECTOR.to_le_bytes());
+    bs[50..52].copy_from_slice(&BACKUP_BOOT_SECTOR.to_le_bytes());
+    bs[64] = 0x80; // drive number
+    bs[66] = 0x29; // extended boot signature
+    bs[67..71].copy_from_slice(&volume_serial().to_le_bytes());
+    bs[71..82].copy_from_slice(&label_bytes(label));
+    bs[82..90].copy_from_slice(b"FAT32   ");
+    bs[510] = 0x55;
+    bs[511] = 0xAA;
+    bs
+}
+
+fn fsinfo_sector(g: &Geometry) -> Vec<u8> {
+    let mut fsi = vec![0u8; g.bytes_per_sector as usize];
+    fsi[0..4].copy_from_slice(&0x41615252u32.to_le_bytes());
+    fsi[484..488].copy_from_slice(&0x61417272u32.to_le_bytes());
+    // Cluster 2 holds the root directory
+    fsi[488..492].copy_from_slice(&(g.cluster_count - 1).to_le_bytes());
+    fsi[492..496].copy_from_slice(&3u32.to_le_bytes());
+    fsi[508..512].copy_from_slice(&0xAA550000u32.to_le_bytes());
+    fsi
+}
+
+fn write_image(path: &str, g: &Geometry, label: Option<&str>) -> std::io::Result<()> {
+    let sector = g.bytes_per_sector as u64;
+    let mut file = OpenOptions::new().write(true).create_new(true).open(path)?;
+
+    // A new file reads back as zeroes, which covers the reserved area, the
+    // rest of each FAT and the root directory cluster
+    file.set_len(g.total_sectors as u64 * sector)?;
+
+    let boot = boot_sector(g, label);
+    let fsinfo = fsinfo_sector(g);
+    for (at, data) in [
+        (0, &boot),
+        (FSINFO_SECTOR, &fsinfo),
+        (BACKUP_BOOT_SECTOR, &boot),
+        (BACKUP_BOOT_SECTOR + FSINFO_SECTOR, &fsinfo),
+    ] {
+        file.seek(SeekFrom::Start(at as u64 * sector))?;
+        file.write_all(data)?;
+    }
+
+    // Entry 0 holds the media byte, entry 1 the end-of-chain marker, entry 2 the root
+    let mut reserved_entries = Vec::with_capacity(12);
+    reserved_entries.extend_from_slice(&(0x0FFFFF00 | MEDIA_FIXED_DISK as u32).to_le_bytes());
+    reserved_entries.extend_from_slice(&0x0FFFFFFFu32.to_le_bytes());
+    reserved_entries.extend_from_slice(&0x0FFFFFFFu32.to_le_bytes());
+    for copy in 0..g.num_fats as u64 {
+        file.seek(SeekFrom::Start((RESERVED_SECTORS as u64 + copy * g.fat_size as u64) * sector))?;
+        file.write_all(&reserved_entries)?;
+    }
+
+    if let Some(label) = label {
+        let root_sector = RESERVED_SECTORS as u64 + g.num_fats as u64 * g.fat_size as u64;
+        let mut entry = [0u8; 32];
+        entry[0..11].copy_from_slice(&label_bytes(Some(label)));
+        entry[11] = 0x08;
+        file.seek(SeekFrom::Start(root_sector * sector))?;
+        file.write_all(&entry)?;
+    }
+
+    file.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::{self, OpenOptions};
+
+    use super::mkfs;
+    use crate::fsck::check;
+    use crate::models::{BootSector, Volume};
+
+    /// Formats an image with `options` and runs fsck over it.
+    fn format_and_check(name: &str, options: &[&str]) {
+        let path = std::env::temp_dir().join(format!("rust_fat32_mkfs_{}_{}.img", std::process::id(), name));
+        let mut args = vec![path.to_string_lossy().into_owned()];
+        args.extend(options.iter().map(|s| s.to_string()));
+        assert_eq!(mkfs(&args), 0);
+
+        let mut file = OpenOptions::new().read(true).write(true).open(&path).unwrap();
+        let bpb = BootSector::new(&mut file);
+        let mut vol = Volume::new(file, bpb);
+        let report = check(&mut vol).unwrap();
+        fs::remove_file(&path).ok();
+
+        assert!(report.is_clean());
+        assert_eq!(report.directories, 1);
+        assert_eq!(report.used_clusters, 1);
+    }
+
+    #[test]
+    fn default_image_checks_clean() {
+        format_and_check("default", &["--size", "64M"]);
+    }
+
+    #[test]
+    fn image_with_options_checks_clean() {
+        format_and_check("options", &["--size", "1G", "--cluster-size", "4K", "--fats", "1", "--label", "TEST"]);
+    }
+
+    #[test]
+    fn large_sectors_check_clean() {
+        format_and_check("sectors", &["--size", "512M", "--sector-size", "4096"]);
+    }
+
+    #[test]
+    fn fat16_sized_volume_is_refused() {
+        let path = std::env::temp_dir().join(format!("rust_fat32_mkfs_{}_small.img", std::process::id()));
+        let args = [path.to_string_lossy().into_owned(), String::from("--size"), String::from("16M")];
+        assert_eq!(mkfs(&args), 1);
+        assert!(!path.exists());
+    }
+}
@@ -1,13 +1,32 @@
-use std::{fs::File, io::Read};
+use std::{collections::BTreeMap, fmt, fs::File, io::Read};
+use crate::journal::Journal;
+use crate::overlay::Overlay;
 #[derive(Copy, Clone)]
 pub struct BootSector {
     pub bpb_byts_per_sec: u16,
     pub bpb_sec_per_clus: u8,
     pub bpb_rsvd_sec_cnt: u16,
     pub bpb_num_fats: u8,
+    pub bpb_root_ent_cnt: u16,
+    pub bpb_tot_sec16: u16,
+    pub bpb_media: u8,
+    pub bpb_fatsz16: u16,
+    pub bpb_sec_per_trk: u16,
+    pub bpb_num_heads: u16,
+    pub bpb_hidd_sec: u32,
     pub bpb_fatsz32: u32,
     pub bpb_tot_sec32: u32,
+    pub bpb_ext_flags: u16,
+    pub bpb_fs_ver: u16,
     pub bpb_root_clus: u32,
+    pub bpb_fs_info: u16,
+    pub bpb_bk_boot_sec: u16,
+    pub bs_oem_name: [u8; 8],
+    pub bs_drv_num: u8,
+    pub bs_boot_sig: u8,
+    pub bs_vol_id: u32,
+    pub bs_vol_lab: [u8; 11],
+    pub bs_fil_sys_type: [u8; 8],
     pub file_size: u64,
 }
 impl BootSector {
@@ -20,13 +39,6 @@ impl BootSector {
             std::process::exit(1);
         }
     }
-    let bpb_byts_per_sec = u16::from_le_bytes([buffer[11], buffer[12]]);
-    let bpb_sec_per_clus = buffer[13];
-    let bpb_rsvd_sec_cnt = u16::from_le_bytes([buffer[14], buffer[15]]);
-    let bpb_num_fats = buffer[16];
-    let bpb_tot_sec32 = u32::from_le_bytes([buffer[32], buffer[33], buffer[34], buffer[35]]);
-    let bpb_fatsz32 = u32::from_le_bytes([buffer[36], buffer[37], buffer[38], buffer[39]]);
-    let bpb_root_clus = u32::from_le_bytes([buffer[44], buffer[45], buffer[46], buffer[47]]);
     let metadata = match image.metadata() {
         Ok(meta) => meta,
         Err(e) => {
@@ -34,16 +46,61 @@ impl BootSector {
             std::process::exit(1);
         }
     };
-    let file_size = metadata.len();
+    BootSector::parse(&buffer, metadata.len())
+    }
+
+    /// Decodes the fields of a boot sector read from an image of `file_size` bytes.
+    pub fn parse(buffer: &[u8], file_size: u64) -> Self {
+    let bpb_byts_per_sec = u16::from_le_bytes([buffer[11], buffer[12]]);
+    let bpb_sec_per_clus = buffer[13];
+    let bpb_rsvd_sec_cnt = u16::from_le_bytes([buffer[14], buffer[15]]);
+    let bpb_num_fats = buffer[16];
+    let bpb_root_ent_cnt = u16::from_le_bytes([buffer[17], buffer[18]]);
+    let bpb_tot_sec16 = u16::from_le_bytes([buffer[19], buffer[20]]);
+    let bpb_media = buffer[21];
+    let bpb_fatsz16 = u16::from_le_bytes([buffer[22], buffer[23]]);
+    let bpb_sec_per_trk = u16::from_le_bytes([buffer[24], buffer[25]]);
+    let bpb_num_heads = u16::from_le_bytes([buffer[26], buffer[27]]);
+    let bpb_hidd_sec = u32::from_le_bytes([buffer[28], buffer[29], buffer[30], buffer[31]]);
+    let bpb_tot_sec32 = u32::from_le_bytes([buffer[32], buffer[33], buffer[34], buffer[35]]);
+    let bpb_fatsz32 = u32::from_le_bytes([buffer[36], buffer[37], buffer[38], buffer[39]]);
+    let bpb_ext_flags = u16::from_le_bytes([buffer[40], buffer[41]]);
+    let bpb_fs_ver = u16::from_le_bytes([buffer[42], buffer[43]]);
+    let bpb_root_clus = u32::from_le_bytes([buffer[44], buffer[45], buffer[46], buffer[47]]);
+    let bpb_fs_info = u16::from_le_bytes([buffer[48], buffer[49]]);
+    let bpb_bk_boot_sec = u16::from_le_bytes([buffer[50], buffer[51]]);
+    let bs_oem_name: [u8; 8] = buffer[3..11].try_into().unwrap();
+    let bs_drv_num = buffer[64];
+    let bs_boot_sig = buffer[66];
+    let bs_vol_id = u32::from_le_bytes([buffer[67], buffer[68], buffer[69], buffer[70]]);
+    let bs_vol_lab: [u8; 11] = buffer[71..82].try_into().unwrap();
+    let bs_fil_sys_type: [u8; 8] = buffer[82..90].try_into().unwrap();
 
     BootSector {
         bpb_byts_per_sec,
         bpb_sec_per_clus,
         bpb_rsvd_sec_cnt,
         bpb_num_fats,
+        bpb_root_ent_cnt,
+        bpb_tot_sec16,
+        bpb_media,
+        bpb_fatsz16,
+        bpb_sec_per_trk,
+        bpb_num_heads,
+        bpb_hidd_sec,
         bpb_fatsz32,
         bpb_tot_sec32,
+        bpb_ext_flags,
+        bpb_fs_ver,
         bpb_root_clus,
+        bpb_fs_info,
+        bpb_bk_boot_sec,
+        bs_oem_name,
+        bs_drv_num,
+        bs_boot_sig,
+        bs_vol_id,
+        bs_vol_lab,
+        bs_fil_sys_type,
         file_size,
     }
     }
@@ -53,7 +110,24 @@ pub struct Volume {
     pub bpb: BootSector,
     pub first_fat_sector: u32,
     pub first_data_sector: u32,
-    pub fat: Vec<u32>
+    pub fat: Vec<u32>,
+    pub journal: Option<Journal>, // stages metadata writes until the command ends
+    pub overlay: Option<Overlay>, // takes every write instead of the image
+}
+
+pub struct DirEntry {
+    pub name: String,
+    pub long_name: Option<String>,
+    pub attr: u8,
+    pub first_cluster: u32,
+    pub size: u32,
+    pub modified: Option<i64>, // seconds since the Unix epoch, None if never set
+}
+
+impl DirEntry {
+    pub fn is_dir(&self) -> bool {
+        (self.attr & 0x10) != 0
+    }
 }
 
 pub struct OpenFile {
@@ -67,6 +141,28 @@ pub struct OpenFile {
     pub mode: FileMode,
 }
 
+/// Why a builtin may not change a directory entry. Read-only and system
+/// entries give way to `-f`; the volume label and long-name slots never do,
+/// since they are not files at all.
+#[derive(Debug)]
+pub enum PermissionError {
+    ReadOnly(String),
+    System(String),
+    VolumeLabel(String),
+    LongName(String),
+}
+
+impl fmt::Display for PermissionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PermissionError::ReadOnly(name) => write!(f, "{}: permission denied: read-only (use -f to override)", name),
+            PermissionError::System(name) => write!(f, "{}: permission denied: system file (use -f to override)", name),
+            PermissionError::VolumeLabel(name) => write!(f, "{}: permission denied: the volume label is not a file", name),
+            PermissionError::LongName(name) => write!(f, "{}: permission denied: a long-name slot is not a file", name),
+        }
+    }
+}
+
 pub enum FileMode {
     Read,
     Write,
@@ -78,4 +174,9 @@ pub struct ShellCore {
     pub cwd_cluster: u32,
     pub cwd_path: String,
     pub open_files: Vec<OpenFile>, // max 10
+    pub last_status: i32,
+    pub errexit: bool, // set -e: stop at the first failing command
+    pub vars: BTreeMap<String, String>, // shell variables, HOME is the image directory `~` expands to
+    pub aliases: BTreeMap<String, String>,
+    pub read_only: bool, // the image was opened without write access
 }
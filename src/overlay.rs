@@ -0,0 +1,195 @@
+use std::collections::BTreeMap;
+use std::fs::{File, OpenOptions};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+const MAGIC: &[u8; 8] = b"FATOVLY1";
+const HEADER_LEN: u64 = 16; // magic, volume serial, sector size
+
+/// A copy-on-write sidecar for an image. Every sector the session writes is
+/// kept here instead, and reads of those sectors come from here; the base
+/// image only changes when the overlay is committed. The file is a header
+/// followed by (sector number, sector) records, one per sector ever written,
+/// so it survives between sessions and only grows with what changed.
+pub struct Overlay {
+    pub path: PathBuf,
+    file: File,
+    serial: u32,
+    sector_size: usize,
+    index: BTreeMap<u32, u64>, // sector number -> offset of its data in the file
+}
+
+impl Overlay {
+    /// Opens the overlay at `path`, creating it if it does not exist. An
+    /// existing overlay must have been made for a volume with the same serial
+    /// number and sector size.
+    pub fn open(path: &Path, serial: u32, sector_size: usize) -> io::Result<Self> {
+        let mut file = OpenOptions::new().read(true).write(true).create(true).truncate(false).open(path)?;
+        let mut raw = Vec::new();
+        file.read_to_end(&mut raw)?;
+
+        let header = header(serial, sector_size);
+        if raw.is_empty() {
+            file.write_all(&header)?;
+            file.sync_all()?;
+        } else if !raw.starts_with(MAGIC) {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "not an overlay file"));
+        } else if raw.get(..HEADER_LEN as usize) != Some(&header[..]) {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "the overlay was made for a different image"));
+        }
+
+        let mut index = BTreeMap::new();
+        let record_len = 4 + sector_size;
+        let mut pos = HEADER_LEN as usize;
+        while pos + record_len <= raw.len() {
+            let sector = u32::from_le_bytes(raw[pos..pos + 4].try_into().unwrap());
+            index.insert(sector, (pos + 4) as u64);
+            pos += record_len;
+        }
+        // A record cut short by a crash is dropped
+        if pos < raw.len() {
+            file.set_len(pos as u64)?;
+        }
+
+        Ok(Overlay { path: path.to_path_buf(), file, serial, sector_size, index })
+    }
+
+    /// Fills `buf` with the overlay's copy of `sector` and returns true, or
+    /// returns false when the session has not written it.
+    pub fn read(&mut self, sector: u32, buf: &mut [u8]) -> io::Result<bool> {
+        let Some(&offset) = self.index.get(&sector) else {
+            return Ok(false);
+        };
+        self.file.seek(SeekFrom::Start(offset))?;
+        self.file.read_exact(buf)?;
+        Ok(true)
+    }
+
+    /// Stores `buf` as the new contents of `sector`, in place when the
+    /// overlay already holds it and as a new record otherwise.
+    pub fn write(&mut self, sector: u32, buf: &[u8]) -> io::Result<()> {
+        if let Some(&offset) = self.index.get(&sector) {
+            self.file.seek(SeekFrom::Start(offset))?;
+            self.file.write_all(buf)?;
+            return Ok(());
+        }
+        let end = self.file.seek(SeekFrom::End(0))?;
+        let mut record = Vec::with_capacity(4 + self.sector_size);
+        record.extend_from_slice(&sector.to_le_bytes());
+        record.extend_from_slice(buf);
+        self.file.write_all(&record)?;
+        self.index.insert(sector, end + 4);
+        Ok(())
+    }
+
+    /// The sectors the overlay holds, in ascending order.
+    pub fn sectors(&self) -> Vec<u32> {
+        self.index.keys().copied().collect()
+    }
+
+    /// The serial number of the volume the overlay was made for.
+    pub fn serial(&self) -> u32 {
+        self.serial
+    }
+
+    /// Forgets every sector, leaving an empty overlay for the volume with
+    /// `serial`, which changes when a committed overlay gave it a new one.
+    pub fn clear(&mut self, serial: u32) -> io::Result<()> {
+        self.file.set_len(0)?;
+        self.file.seek(SeekFrom::Start(0))?;
+        self.file.write_all(&header(serial, self.sector_size))?;
+        self.file.sync_all()?;
+        self.serial = serial;
+        self.index.clear();
+        Ok(())
+    }
+
+    pub fn file(&self) -> &File {
+        &self.file
+    }
+}
+
+fn header(serial: u32, sector_size: usize) -> Vec<u8> {
+    let mut header = Vec::with_capacity(HEADER_LEN as usize);
+    header.extend_from_slice(MAGIC);
+    header.extend_from_slice(&serial.to_le_bytes());
+    header.extend_from_slice(&(sector_size as u32).to_le_bytes());
+    header
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+
+    /// An overlay path in the temp directory, removed when dropped.
+    struct Scratch(PathBuf);
+
+    impl Drop for Scratch {
+        fn drop(&mut self) {
+            fs::remove_file(&self.0).ok();
+        }
+    }
+
+    fn scratch(name: &str) -> Scratch {
+        Scratch(std::env::temp_dir().join(format!("rust_fat32_overlay_{}_{}.ovl", std::process::id(), name)))
+    }
+
+    #[test]
+    fn written_sectors_read_back_and_persist() {
+        let s = scratch("persist");
+        let mut overlay = Overlay::open(&s.0, 0x1234, 512).unwrap();
+        let mut buf = [0u8; 512];
+        assert!(!overlay.read(7, &mut buf).unwrap());
+
+        overlay.write(7, &[0xAA; 512]).unwrap();
+        overlay.write(3, &[0xBB; 512]).unwrap();
+        overlay.write(7, &[0xCC; 512]).unwrap();
+        drop(overlay);
+
+        let mut overlay = Overlay::open(&s.0, 0x1234, 512).unwrap();
+        assert_eq!(overlay.sectors(), vec![3, 7]);
+        assert!(overlay.read(7, &mut buf).unwrap());
+        assert_eq!(buf, [0xCC; 512]);
+        // Rewriting a sector reuses its record
+        assert_eq!(fs::metadata(&s.0).unwrap().len(), HEADER_LEN + 2 * (4 + 512));
+    }
+
+    #[test]
+    fn torn_record_is_dropped() {
+        let s = scratch("torn");
+        let mut overlay = Overlay::open(&s.0, 1, 512).unwrap();
+        overlay.write(5, &[0xAA; 512]).unwrap();
+        overlay.write(6, &[0xBB; 512]).unwrap();
+        drop(overlay);
+        let len = fs::metadata(&s.0).unwrap().len();
+        OpenOptions::new().write(true).open(&s.0).unwrap().set_len(len - 100).unwrap();
+
+        let overlay = Overlay::open(&s.0, 1, 512).unwrap();
+        assert_eq!(overlay.sectors(), vec![5]);
+        assert_eq!(fs::metadata(&s.0).unwrap().len(), HEADER_LEN + 4 + 512);
+    }
+
+    #[test]
+    fn overlay_for_another_volume_is_refused() {
+        let s = scratch("serial");
+        drop(Overlay::open(&s.0, 1, 512).unwrap());
+        assert!(Overlay::open(&s.0, 2, 512).is_err());
+        assert!(Overlay::open(&s.0, 1, 4096).is_err());
+    }
+
+    #[test]
+    fn clear_forgets_sectors_and_takes_the_new_serial() {
+        let s = scratch("clear");
+        let mut overlay = Overlay::open(&s.0, 1, 512).unwrap();
+        overlay.write(9, &[0xAA; 512]).unwrap();
+        overlay.clear(2).unwrap();
+        assert!(overlay.sectors().is_empty());
+        assert_eq!(overlay.serial(), 2);
+        drop(overlay);
+
+        assert!(Overlay::open(&s.0, 1, 512).is_err());
+        assert!(Overlay::open(&s.0, 2, 512).unwrap().sectors().is_empty());
+    }
+}
@@ -1,28 +1,45 @@
 use std::{env, path::Path};
 // use std::io::{stdin, stdout, Write};
+use crate::builtins::glob_match;
 use crate::commands::*;
+use crate::models::ShellCore;
 
 
 pub enum Token {
     Word(String),
+    Quoted(String), // came from '...' or "...", so it is never glob expanded
     Argument(String),
     EnvVar(String),
     Pipe,
     RedirOut,
     RedirIn,
     Background,
+    Sequence,
+    AndIf,
+    OrIf,
     Tilde(String),
 }
 
 impl PartialEq for Token {
     fn eq(&self, other: &Self) -> bool {
-        match (self, other) {
-            (Token::Pipe, Token::Pipe) => true,
-            _ => false,
-        }
+        matches!(
+            (self, other),
+            (Token::Pipe, Token::Pipe)
+                | (Token::Sequence, Token::Sequence)
+                | (Token::AndIf, Token::AndIf)
+                | (Token::OrIf, Token::OrIf)
+        )
     }
 }
 
+/// How a pipeline in a command list depends on the status of the one before it.
+#[derive(Clone, Copy, PartialEq)]
+pub enum ListOp {
+    Always, // first pipeline, or after `;`
+    And,    // after `&&`: only runs if the previous status was 0
+    Or,     // after `||`: only runs if the previous status was non-zero
+}
+
 pub fn tokenize(input: &str) -> Vec<Token> {
     let mut tokens = Vec::new();
     let mut cur = String::new();
@@ -40,8 +57,8 @@ pub fn tokenize(input: &str) -> Vec<Token> {
             other => {
                 if other.starts_with('~') {
                     tokens.push(Token::Tilde(other.to_string()));
-                } else if other.starts_with('$') {
-                    tokens.push(Token::EnvVar(other[1..].to_string()));
+                } else if let Some(name) = other.strip_prefix('$') {
+                    tokens.push(Token::EnvVar(name.to_string()));
                 } else if other.starts_with('-') {
                     tokens.push(Token::Argument(other.to_string()));
                 } else {
@@ -59,6 +76,17 @@ pub fn tokenize(input: &str) -> Vec<Token> {
             }
             ' ' | '\t' | '\n' => {
             }
+            ';' => {
+                push_token(&mut tokens, cur.clone());
+                cur.clear();
+                tokens.push(Token::Sequence);
+            }
+            '&' | '|' if chars.peek() == Some(&ch) => {
+                chars.next();
+                push_token(&mut tokens, cur.clone());
+                cur.clear();
+                tokens.push(if ch == '&' { Token::AndIf } else { Token::OrIf });
+            }
             '"' | '\'' => {
                 let quote = ch;
                 let mut collected = String::new();
@@ -77,10 +105,10 @@ pub fn tokenize(input: &str) -> Vec<Token> {
                 }
                 if !cur.is_empty() {
                     cur.push_str(&collected);
-                    push_token(&mut tokens, cur.clone());
+                    tokens.push(Token::Quoted(cur.clone()));
                     cur.clear();
                 } else {
-                    push_token(&mut tokens, collected);
+                    tokens.push(Token::Quoted(collected));
                 }
             }
             _ => {
@@ -95,30 +123,85 @@ pub fn tokenize(input: &str) -> Vec<Token> {
     tokens
 }
 
-pub fn expand_tokens(tokens: Vec<Token>) -> Vec<String> {
-    let mut expanded_tokens: Vec<String> = Vec::new();
+/// Replaces an alias in command position with the tokens of its value. An
+/// alias is not expanded again inside its own value, so `alias ls='ls -l'` works.
+pub fn expand_aliases(tokens: Vec<Token>, shell: &ShellCore) -> Vec<Token> {
+    let mut expanded: Vec<Token> = Vec::new();
+    let mut at_command = true;
+    let mut pending: Vec<(Token, Vec<String>)> = tokens.into_iter().rev().map(|t| (t, Vec::new())).collect();
+
+    while let Some((token, seen)) = pending.pop() {
+        if at_command
+            && let Token::Word(word) = &token
+            && !seen.contains(word)
+            && let Some(value) = shell.aliases.get(word)
+        {
+            let mut seen = seen.clone();
+            seen.push(word.clone());
+            for alias_token in tokenize(value).into_iter().rev() {
+                pending.push((alias_token, seen.clone()));
+            }
+            continue;
+        }
+
+        at_command = matches!(
+            token,
+            Token::Pipe | Token::Sequence | Token::AndIf | Token::OrIf | Token::Background
+        );
+        expanded.push(token);
+    }
+    expanded
+}
+
+/// Splits a token stream at `;`, `&&` and `||` into the pipelines of a command
+/// list, each tagged with the operator that preceded it.
+pub fn split_list(tokens: Vec<Token>) -> Vec<(ListOp, Vec<Token>)> {
+    let mut list: Vec<(ListOp, Vec<Token>)> = vec![(ListOp::Always, Vec::new())];
+    for token in tokens {
+        match token {
+            Token::Sequence => list.push((ListOp::Always, Vec::new())),
+            Token::AndIf => list.push((ListOp::And, Vec::new())),
+            Token::OrIf => list.push((ListOp::Or, Vec::new())),
+            other => list.last_mut().unwrap().1.push(other),
+        }
+    }
+    list.retain(|(_, pipeline)| !pipeline.is_empty());
+    list
+}
+
+/// Expands variables, `~` and globs, pairing each resulting word with whether
+/// it was quoted, since a quoted `|`, `>`, `<` or `&` is an ordinary word.
+pub fn expand_tokens(tokens: Vec<Token>, shell: &mut ShellCore) -> Vec<(String, bool)> {
+    let mut expanded_tokens: Vec<(String, bool)> = Vec::new();
     let mut prev_token: Option<Token> = None;
 
     for token in tokens {
         match token {
             Token::EnvVar(name) => {
-                if let Ok(val) = env::var(&name) {
-                    expanded_tokens.push(val);
+                if name == "?" {
+                    expanded_tokens.push((shell.last_status.to_string(), false));
+                    prev_token = Some(Token::EnvVar(name));
+                } else if let Some(val) = shell.vars.get(&name) {
+                    expanded_tokens.push((val.clone(), false));
+                    prev_token = Some(Token::EnvVar(name));
+                } else if let Ok(val) = env::var(&name) {
+                    expanded_tokens.push((val, false));
                     prev_token = Some(Token::EnvVar(name));
                 } else {
-                    expanded_tokens.push(String::new());
+                    expanded_tokens.push((String::new(), false));
                     prev_token = Some(Token::EnvVar(name));
                 }
             }
             Token::Tilde(s) => {
-                let home = env::var("HOME").unwrap_or_else(|_| String::from("/"));
+                // `~` is a directory inside the image: the shell's HOME, the root by default
+                let home = shell.vars.get("HOME").map(|h| h.as_str()).unwrap_or("/");
                 if s == "~" {
-                    expanded_tokens.push(home);
+                    expanded_tokens.push((home.to_string(), false));
                 } else if let Some(rest) = s.strip_prefix("~/") {
-                    let full = format!("{}/{}", home, rest);
-                    expanded_tokens.push(full);
+                    let full = format!("{}/{}", home.trim_end_matches('/'), rest);
+                    expanded_tokens.extend(expand_glob(&full, shell).into_iter().map(|s| (s, false)));
                 } else {
-                    expanded_tokens.push(s.clone());
+                    expanded_tokens.push((s.clone(), false));
                 }
                 prev_token = Some(Token::Tilde(s));
             }
@@ -126,40 +209,114 @@ pub fn expand_tokens(tokens: Vec<Token>) -> Vec<String> {
             Token::Word(s) => {
                 if prev_token.is_none() || prev_token == Some(Token::Pipe) {
                     let program = resolve_path(&s);
-                    expanded_tokens.push(program);
+                    expanded_tokens.push((program, false));
                     prev_token = Some(Token::Word(s));
                     continue;
                 } else {
-                    expanded_tokens.push(s);
+                    expanded_tokens.extend(expand_glob(&s, shell).into_iter().map(|s| (s, false)));
                     prev_token = Some(Token::Word(String::new()));
                     continue;
                 }
             }
+            Token::Quoted(s) => {
+                expanded_tokens.push((s, true));
+                prev_token = Some(Token::Word(String::new()));
+            }
             Token::Argument(s) => {
-                expanded_tokens.push(s);
+                expanded_tokens.push((s, false));
                 prev_token = Some(Token::Argument(String::new()));
             }
             Token::Pipe => {
-                expanded_tokens.push("|".to_string());
+                expanded_tokens.push(("|".to_string(), false));
                 prev_token = Some(Token::Pipe);
             }
             Token::RedirOut => {
-                expanded_tokens.push(">".to_string());
+                expanded_tokens.push((">".to_string(), false));
                 prev_token = Some(Token::RedirOut);
             }
             Token::RedirIn => {
-                expanded_tokens.push("<".to_string());
+                expanded_tokens.push(("<".to_string(), false));
                 prev_token = Some(Token::RedirIn);
             }
             Token::Background => {
-                expanded_tokens.push("&".to_string());
+                expanded_tokens.push(("&".to_string(), false));
                 prev_token = Some(Token::Background);
             }
+            Token::Sequence | Token::AndIf | Token::OrIf => {
+                // Lists are split by the executor before expansion
+                prev_token = Some(token);
+            }
         }
     }
     expanded_tokens
 }
 
+/// Expands `*`, `?` and `[...]` in each component of a path against the
+/// image's directories. Names match case-insensitively on either the long or
+/// the 8.3 name, and expand to the 8.3 name the other builtins look up. Like
+/// bash, a pattern that matches nothing is passed on unchanged.
+fn expand_glob(word: &str, shell: &mut ShellCore) -> Vec<String> {
+    if !word.contains(['*', '?', '[']) {
+        return vec![word.to_string()];
+    }
+
+    let root = shell.vol.bpb.bpb_root_clus;
+    let absolute = word.starts_with('/');
+    let components: Vec<&str> = word.split('/').filter(|c| !c.is_empty()).collect();
+    // Each candidate is the path built so far and the cluster it names
+    let mut candidates: Vec<(String, u32)> = vec![(
+        String::from(if absolute { "/" } else { "" }),
+        if absolute { root } else { shell.cwd_cluster },
+    )];
+
+    for (i, component) in components.iter().enumerate() {
+        let last = i + 1 == components.len();
+        let mut next = Vec::new();
+
+        for (prefix, cluster) in candidates {
+            let join = |name: &str| {
+                if prefix.is_empty() || prefix.ends_with('/') { format!("{}{}", prefix, name) } else { format!("{}/{}", prefix, name) }
+            };
+
+            if !component.contains(['*', '?', '[']) {
+                if last {
+                    next.push((join(component), cluster));
+                } else if let Some(dir) = shell.vol.resolve_directory(cluster, component) {
+                    next.push((join(component), dir));
+                }
+                continue;
+            }
+
+            let Ok(entries) = shell.vol.list_directory(cluster) else { continue };
+            let mut matched: Vec<(String, u32)> = entries
+                .into_iter()
+                .filter(|e| e.name != "." && e.name != "..")
+                .filter(|e| last || e.is_dir())
+                .filter(|e| {
+                    glob_match(component, &e.name, true)
+                        || e.long_name.as_deref().is_some_and(|long| glob_match(component, long, true))
+                })
+                .map(|e| {
+                    let dir = if e.first_cluster == 0 { root } else { e.first_cluster };
+                    (join(&e.name), dir)
+                })
+                .collect();
+            matched.sort();
+            next.extend(matched);
+        }
+        candidates = next;
+    }
+
+    if candidates.is_empty() {
+        return vec![word.to_string()];
+    }
+    let trailing_slash = word.ends_with('/');
+    candidates
+        .into_iter()
+        .map(|(path, _)| if trailing_slash { format!("{}/", path) } else { path })
+        .collect()
+}
+
 fn resolve_path(s: &str) -> String {
     if is_built_in(s) {
         return s.to_string();